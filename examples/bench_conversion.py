@@ -0,0 +1,65 @@
+import json
+import time
+
+import starlark as sl
+
+
+# Measures the conversion of large nested payloads between Python and Starlark.
+#
+# The time of a json.dumps/json.loads round trip of the same payload is shown
+# as a point of reference only. It does not include building Starlark values,
+# so it is not the cost of converting by way of JSON.
+
+A_STAR = """
+def identity(x):
+    return x
+"""
+
+
+def make_payload(n):
+    return [
+        {
+            "id": i,
+            "name": f"item-{i}",
+            "price": i * 0.25,
+            "active": i % 2 == 0,
+            "parent": None,
+            "tags": ["a", "b", "c"],
+        }
+        for i in range(n)]
+
+
+def bench(name, f, repeat=5):
+    best = min(timeit(f) for _ in range(repeat))
+    print(f"{name:<30} {best * 1000:8.1f} ms")
+
+
+def timeit(f):
+    start = time.perf_counter()
+    f()
+    return time.perf_counter() - start
+
+
+payload = make_payload(100_000)
+
+glb = sl.Globals.standard()
+mod = sl.Module()
+sl.eval(mod, sl.parse("a.star", A_STAR), glb)
+fmod = mod.freeze()
+
+
+def set_item():
+    sl.Module()["payload"] = payload
+
+
+def call():
+    assert len(fmod.call("identity", payload)) == len(payload)
+
+
+def json_round_trip():
+    json.loads(json.dumps(payload))
+
+
+bench("Module.__setitem__", set_item)
+bench("FrozenModule.call", call)
+bench("json round trip (reference)", json_round_trip)
@@ -32,3 +32,9 @@ ast = sl.parse("a.star", A_STAR)
 glb = sl.Globals.standard()
 mod = sl.Module()
 val = sl.eval(mod, ast, glb)
+
+# A step budget turns the first one into an exception.
+try:
+    sl.eval(mod, sl.parse("b.star", "dos(30)"), glb, max_steps=100_000)
+except sl.StepLimitExceeded as e:
+    print(f"stopped: {e}")
@@ -26,47 +26,120 @@ extern crate starlark;
 extern crate starlark_derive;
 extern crate thiserror;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{self, Display};
-use std::sync::Mutex;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
 
 use crate::pyo3::create_exception;
-use crate::pyo3::exceptions::PyException;
+use crate::pyo3::exceptions::{
+    PyAttributeError, PyException, PyKeyError, PyRuntimeError, PyValueError,
+};
+use crate::pyo3::intern;
 use crate::pyo3::prelude::*;
 
 use gazebo::prelude::*;
 
-use crate::starlark::collections::SmallMap;
+use crate::starlark::collections::{SmallMap, StarlarkHasher};
 use crate::starlark::typing::AstModuleTypecheck;
 use allocative::Allocative;
 use dupe::Dupe;
+use num_bigint::BigInt;
 use pyo3::sync::MutexExt;
-use pyo3::types::{PyDict, PyList, PyTuple};
+use pyo3::types::{
+    PyBool, PyCFunction, PyDict, PyFloat, PyInt, PyIterator, PyList, PyString, PyTuple, PyType,
+};
+use pyo3::PyTypeInfo;
 use starlark::analysis::AstModuleLint;
+use starlark::codemap::FileSpanRef;
 use starlark::environment::GlobalsBuilder;
 use starlark::eval::Arguments;
+use starlark::eval::BeforeStmtFuncDyn;
+use starlark::eval::Evaluator;
 use starlark::starlark_simple_value;
+use starlark::typing::{
+    ParamSpec, Ty, TyCallable, TyStarlarkValue, TyUser, TyUserFields, TyUserParams,
+};
 use starlark::values::dict::Dict;
 use starlark::values::dict::DictRef;
+use starlark::values::enumeration::{EnumType, EnumValue, FrozenEnumType};
+use starlark::values::float::StarlarkFloat;
 use starlark::values::list::{AllocList, ListRef};
 use starlark::values::record::Record;
-use starlark::values::structs::StructRef;
-use starlark::values::tuple::TupleRef;
+use starlark::values::structs::{AllocStruct, StructRef};
+use starlark::values::tuple::{AllocTuple, TupleRef};
+use starlark::values::typing::{TypeInstanceId, TypeMatcher, TypeMatcherFactory};
 use starlark::values::FreezeResult;
+use starlark::values::FrozenHeap;
+use starlark::values::FrozenHeapRef;
 use starlark::values::Heap;
 use starlark::values::NoSerialize;
+use starlark::values::OwnedFrozenValue;
 use starlark::values::ProvidesStaticType;
 use starlark::values::StarlarkValue;
+use starlark::values::UnpackValue;
+use starlark::values::ValueError;
 use starlark::values::Value;
 use starlark::values::ValueLike;
 use starlark_derive::starlark_value;
 use thiserror::Error;
 
 create_exception!(starlark, StarlarkError, PyException);
+create_exception!(
+    starlark,
+    StarlarkSyntaxError,
+    StarlarkError,
+    "Raised when Starlark source code fails to parse."
+);
+create_exception!(
+    starlark,
+    StarlarkEvalError,
+    StarlarkError,
+    "Raised when evaluation of Starlark code fails."
+);
+create_exception!(
+    starlark,
+    StarlarkTypeError,
+    StarlarkEvalError,
+    "Raised when an operation is applied to values of unsuitable types, \
+    or when a function is called with unsuitable arguments. Violations of \
+    runtime type annotations are not distinguished by starlark-rust and \
+    are reported as :exc:`StarlarkEvalError`."
+);
+create_exception!(
+    starlark,
+    StarlarkFreezeError,
+    StarlarkError,
+    "Raised when a :class:`Module` cannot be frozen."
+);
+create_exception!(
+    starlark,
+    StarlarkConversionError,
+    StarlarkError,
+    "Raised when a value cannot be converted between Starlark and Python."
+);
+create_exception!(starlark, StepLimitExceeded, StarlarkEvalError);
+create_exception!(starlark, EvaluationCancelled, StarlarkEvalError);
+create_exception!(starlark, MemoryLimitExceeded, StarlarkEvalError);
+create_exception!(
+    starlark,
+    StackOverflowError,
+    StarlarkEvalError,
+    "Raised when the Starlark call stack grows too deep."
+);
 
 mod decimal;
+mod signature;
+mod traceback;
 
 use decimal::{decimal_module, decimal_to_python, python_to_decimal, DecimalValue};
+use signature::Signature;
+use traceback::{add_starlark_traceback, set_starlark_tracebacks, starlark_traceback};
 
 // {{{ value conversion
 
@@ -113,23 +186,209 @@ fn serde_to_starlark(x: serde_json::Value, heap: &Heap) -> anyhow::Result<Value<
 
 // }}}
 
-// Converts Starlark values to Python objects.
-//
-// For custom types (like RustDecimal) and nested structures (dict/list/tuple), we handle
-// conversion directly rather than going through JSON. This allows custom types to work
-// in nested structures while preserving their semantics (e.g., RustDecimal precision).
-// Primitive types still use the JSON fallback path for simplicity.
-fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
+static PRESERVE_TUPLES: AtomicBool = AtomicBool::new(false);
+
+/// Set the default for whether tuples are preserved in the conversion
+/// between Starlark and Python (see :ref:`object-conversion`). This is a
+/// global setting that may be overridden per :class:`Module` and per
+/// evaluation.
+///
+/// .. versionadded:: 2025.2.6
+#[pyfunction]
+#[pyo3(signature = (enabled=true), text_signature = "(enabled: bool = True) -> None")]
+fn set_preserve_tuples(enabled: bool) {
+    PRESERVE_TUPLES.store(enabled, Ordering::Relaxed);
+}
+
+static PRESERVE_STRUCTS: AtomicBool = AtomicBool::new(false);
+
+/// Set the default for whether Starlark structs and records are converted
+/// to :class:`StarlarkStruct` and :class:`StarlarkRecord` objects rather than
+/// to dictionaries (see :ref:`object-conversion`). This is a global setting
+/// that may be overridden per :class:`Module` and per evaluation.
+///
+/// .. versionadded:: 2025.2.6
+#[pyfunction]
+#[pyo3(signature = (enabled=true), text_signature = "(enabled: bool = True) -> None")]
+fn set_preserve_structs(enabled: bool) {
+    PRESERVE_STRUCTS.store(enabled, Ordering::Relaxed);
+}
+
+// Settings for the conversion of values between Starlark and Python.
+// During evaluation, these are available as the evaluator's 'extra',
+// for use by Python callables.
+#[derive(Clone, ProvidesStaticType)]
+struct ConversionOptions {
+    preserve_tuples: bool,
+    preserve_structs: bool,
+    converters: Option<Arc<Mutex<Converters>>>,
+    // The module being called by FrozenModule.call, whose enum types are
+    // used in converting values to Starlark.
+    frozen_module: Option<starlark::environment::FrozenModule>,
+    // The heap keeping alive the frozen values being converted to Python,
+    // which is required for converting functions.
+    owner: Option<FrozenHeapRef>,
+    // While a frozen function is being called, the heaps keeping alive the
+    // frozen values it may pass to Python callables: that of the function
+    // and those of functions passed in from Python. Used for converting
+    // functions in place of owner.
+    call_heaps: Option<Arc<Mutex<Vec<FrozenHeapRef>>>>,
+    // The number of converters applied to get the object being converted
+    // to Starlark.
+    converter_depth: usize,
+    // The name of the module being evaluated, as passed to Python
+    // callables in a CallContext.
+    module_name: Option<String>,
+}
+
+impl ConversionOptions {
+    // Resolves unset options from the global defaults.
+    fn new(
+        preserve_tuples: Option<bool>,
+        preserve_structs: Option<bool>,
+        converters: Option<&ConverterRegistry>,
+    ) -> ConversionOptions {
+        ConversionOptions {
+            preserve_tuples: preserve_tuples
+                .unwrap_or_else(|| PRESERVE_TUPLES.load(Ordering::Relaxed)),
+            preserve_structs: preserve_structs
+                .unwrap_or_else(|| PRESERVE_STRUCTS.load(Ordering::Relaxed)),
+            converters: converters.map(|registry| registry.0.clone()),
+            frozen_module: None,
+            owner: None,
+            call_heaps: None,
+            converter_depth: 0,
+            module_name: None,
+        }
+    }
+
+    // Dictionary keys must be hashable, which Starlark lists are not
+    // (and Python lists are not), so tuples are always preserved in keys.
+    fn for_keys(&self) -> ConversionOptions {
+        let mut options = self.clone();
+        options.preserve_tuples = true;
+        options
+    }
+
+    fn from_evaluator(eval: &Evaluator) -> ConversionOptions {
+        eval.extra
+            .and_then(|extra| extra.downcast_ref::<ConversionOptions>())
+            .cloned()
+            .unwrap_or_else(|| ConversionOptions::new(None, None, None))
+    }
+
+    // Like from_evaluator, for the innermost evaluation running on this
+    // thread, where the evaluator is out of reach (as in get_attr).
+    fn current() -> ConversionOptions {
+        EVAL_OPTIONS
+            .with_borrow(|stack| stack.last().cloned())
+            .unwrap_or_else(|| ConversionOptions::new(None, None, None))
+    }
+}
+
+thread_local! {
+    // The options of the evaluations running on this thread, innermost last.
+    static EVAL_OPTIONS: RefCell<Vec<ConversionOptions>> = const { RefCell::new(Vec::new()) };
+}
+
+// Makes options available from ConversionOptions::current while alive.
+// Also forgets any attribute error left over from a previous evaluation.
+struct EvalOptionsGuard;
+
+impl EvalOptionsGuard {
+    fn new(options: &ConversionOptions) -> EvalOptionsGuard {
+        EVAL_OPTIONS.with_borrow_mut(|stack| stack.push(options.clone()));
+        ATTRIBUTE_ERROR.set(None);
+        EvalOptionsGuard
+    }
+}
+
+impl Drop for EvalOptionsGuard {
+    fn drop(&mut self) {
+        EVAL_OPTIONS.with_borrow_mut(|stack| stack.pop());
+    }
+}
+
+// Where values converted to Starlark are allocated. Enum types are looked up
+// among the globals of a module, and functions can only be passed to a
+// module, whose frozen heap keeps them alive.
+#[derive(Clone, Copy)]
+enum ConversionTarget<'v> {
+    Module(&'v starlark::environment::Module),
+    Heap(&'v Heap),
+}
+
+impl<'v> ConversionTarget<'v> {
+    fn heap(self) -> &'v Heap {
+        match self {
+            ConversionTarget::Module(module) => module.heap(),
+            ConversionTarget::Heap(heap) => heap,
+        }
+    }
+
+    fn module(self) -> Option<&'v starlark::environment::Module> {
+        match self {
+            ConversionTarget::Module(module) => Some(module),
+            ConversionTarget::Heap(_) => None,
+        }
+    }
+}
+
+// Converts Starlark values to Python objects, using a registered converter
+// for the type of the value if there is one.
+fn value_to_pyobject<'v>(
+    value: Value<'v>,
+    heap: &'v Heap,
+    options: &ConversionOptions,
+) -> PyResult<Py<PyAny>> {
+    if let Some(from_starlark) = from_starlark_converter(options, value.get_type()) {
+        let obj = builtin_value_to_pyobject(value, heap, options)?;
+        return Python::attach(|py| from_starlark.call1(py, (obj,)));
+    }
+    builtin_value_to_pyobject(value, heap, options)
+}
+
+// Primitives, custom types (like RustDecimal) and nested structures (dict/list/tuple)
+// are converted directly rather than going through JSON. This preserves values JSON
+// cannot represent (e.g. big integers, NaN), and allows custom types to work in
+// nested structures while preserving their semantics (e.g., RustDecimal precision).
+// Only values of other types use the JSON fallback path.
+fn builtin_value_to_pyobject<'v>(
+    value: Value<'v>,
+    heap: &'v Heap,
+    options: &ConversionOptions,
+) -> PyResult<Py<PyAny>> {
+    if value.is_none() {
+        return Python::attach(|py| Ok(py.None()));
+    }
+    if let Some(b) = value.unpack_bool() {
+        return Python::attach(|py| Ok(PyBool::new(py, b).to_owned().into_any().unbind()));
+    }
+    if let Some(i) = value.unpack_i32() {
+        return Python::attach(|py| Ok(i.into_pyobject(py)?.into_any().unbind()));
+    }
+    // Unpacking to BigInt does not fail, it only rejects non-integers.
+    if let Ok(Some(i)) = BigInt::unpack_value(value) {
+        return Python::attach(|py| Ok(i.into_pyobject(py)?.into_any().unbind()));
+    }
+    if let Some(f) = value.downcast_ref::<StarlarkFloat>() {
+        return Python::attach(|py| Ok(PyFloat::new(py, f.0).into_any().unbind()));
+    }
+    if let Some(s) = value.unpack_str() {
+        return Python::attach(|py| Ok(PyString::new(py, s).into_any().unbind()));
+    }
+
     if let Some(decimal) = value.downcast_ref::<DecimalValue>() {
         return decimal_to_python(decimal);
     }
 
     if let Some(dict) = DictRef::from_value(value) {
         return Python::attach(|py| {
+            let key_options = options.for_keys();
             let py_dict = PyDict::new(py);
             for (k, v) in dict.iter() {
-                let py_key = value_to_pyobject(k)?.into_bound(py);
-                let py_val = value_to_pyobject(v)?.into_bound(py);
+                let py_key = value_to_pyobject(k, heap, &key_options)?.into_bound(py);
+                let py_val = value_to_pyobject(v, heap, options)?.into_bound(py);
                 py_dict.set_item(py_key, py_val)?;
             }
             Ok(py_dict.into_any().unbind())
@@ -140,7 +399,7 @@ fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
         return Python::attach(|py| {
             let mut elements = Vec::with_capacity(list.len());
             for item in list.iter() {
-                elements.push(value_to_pyobject(item)?);
+                elements.push(value_to_pyobject(item, heap, options)?);
             }
             let py_list = PyList::new(py, elements.into_iter().map(|obj| obj.into_bound(py)))?;
             Ok(py_list.into_any().unbind())
@@ -151,7 +410,11 @@ fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
         return Python::attach(|py| {
             let mut elements = Vec::with_capacity(tuple.len());
             for item in tuple.iter() {
-                elements.push(value_to_pyobject(item)?);
+                elements.push(value_to_pyobject(item, heap, options)?);
+            }
+            if options.preserve_tuples {
+                let py_tuple = PyTuple::new(py, elements)?;
+                return Ok(py_tuple.into_any().unbind());
             }
             // Convert to list for backwards compatibility with JSON path
             let py_list = PyList::new(py, elements.into_iter().map(|obj| obj.into_bound(py)))?;
@@ -160,11 +423,19 @@ fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
     }
 
     if let Some(struct_ref) = StructRef::from_value(value) {
+        if options.preserve_structs {
+            let fields = convert_fields(
+                struct_ref.iter().map(|(key, val)| (key.as_str(), val)),
+                heap,
+                options,
+            )?;
+            return Python::attach(|py| Ok(Py::new(py, StarlarkStruct(fields))?.into_any()));
+        }
         return Python::attach(|py| {
             let py_dict = PyDict::new(py);
             for (key, val) in struct_ref.iter() {
                 let py_key = key.as_str();
-                let py_val = value_to_pyobject(val)?.into_bound(py);
+                let py_val = value_to_pyobject(val, heap, options)?.into_bound(py);
                 py_dict.set_item(py_key, py_val)?;
             }
             Ok(py_dict.into_any().unbind())
@@ -172,21 +443,46 @@ fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
     }
 
     if let Some(record) = Record::from_value(value) {
+        let cls = record_class(options, value);
+        if cls.is_none() && options.preserve_structs {
+            let record = StarlarkRecord {
+                record_type: Ty::of_value(value).to_string(),
+                fields: convert_fields(record.iter(), heap, options)?,
+            };
+            return Python::attach(|py| Ok(Py::new(py, record)?.into_any()));
+        }
         return Python::attach(|py| {
             let py_dict = PyDict::new(py);
             for (key, val) in record.iter() {
-                let py_val = value_to_pyobject(val)?.into_bound(py);
+                let py_val = value_to_pyobject(val, heap, options)?.into_bound(py);
                 py_dict.set_item(key, py_val)?;
             }
-            Ok(py_dict.into_any().unbind())
+            match cls {
+                Some(cls) => cls.call(py, (), Some(&py_dict)),
+                None => Ok(py_dict.into_any().unbind()),
+            }
         });
     }
 
+    if EnumValue::from_value(value).is_some() {
+        return enum_value_to_pyobject(value, heap, options);
+    }
+
     if let Some(opaque) = StarlarkOpaquePythonObject::from_value(value) {
         return Python::attach(|py| Ok(opaque.obj.clone_ref(py)));
     }
 
-    let json_val = convert_anyhow_err(value.to_json())?;
+    if let Some(callable) = value.downcast_ref::<PythonCallableValue>() {
+        return Python::attach(|py| Ok(callable.callable.clone_ref(py)));
+    }
+    if let Some(native_type) = value.downcast_ref::<NativeTypeValue>() {
+        return Python::attach(|py| Ok(native_type.constructor.callable.clone_ref(py)));
+    }
+    if value.get_type() == "function" {
+        return function_to_pyobject(value, options);
+    }
+
+    let json_val = convert_conversion_err(value.to_json())?;
     Python::attach(|py| {
         let json = py.import("json")?;
         json.getattr("loads")?.call1((json_val,))?.extract()
@@ -195,21 +491,66 @@ fn value_to_pyobject(value: Value) -> PyResult<Py<PyAny>> {
 
 // Converts Python objects to Starlark values.
 //
-// Mirrors value_to_pyobject's approach: handle custom types and nested structures
-// directly, falling back to JSON for primitives. This enables custom types like
-// RustDecimal to work correctly in nested structures.
-fn pyobject_to_value<'v>(obj: Bound<PyAny>, heap: &'v Heap) -> PyResult<Value<'v>> {
+// Mirrors value_to_pyobject's approach: handle registered converters, primitives,
+// custom types and nested structures directly, falling back to JSON only for other
+// objects. This enables custom types like RustDecimal to work correctly in nested
+// structures.
+fn pyobject_to_value<'v>(
+    obj: Bound<PyAny>,
+    target: ConversionTarget<'v>,
+    options: &ConversionOptions,
+) -> PyResult<Value<'v>> {
+    let heap = target.heap();
+    if obj.is_none() {
+        return Ok(Value::new_none());
+    }
+    if let Some(converted) = convert_to_starlark(options, &obj)? {
+        let mut options = options.clone();
+        options.converter_depth += 1;
+        return pyobject_to_value(converted, target, &options);
+    }
+    // bool is a subclass of int, but a separate type in Starlark.
+    if let Ok(b) = obj.downcast::<PyBool>() {
+        return Ok(Value::new_bool(b.is_true()));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        // Members of int enums and flags may match Starlark enum values.
+        if !obj.is_exact_instance_of::<PyInt>() {
+            if let Some(value) = python_enum_to_value(&obj, target, options)? {
+                return Ok(value);
+            }
+        }
+        if let Ok(i) = obj.extract::<i32>() {
+            return Ok(heap.alloc(i));
+        }
+        return Ok(heap.alloc(obj.extract::<BigInt>()?));
+    }
+    if let Ok(f) = obj.downcast::<PyFloat>() {
+        return Ok(heap.alloc(f.value()));
+    }
+    if let Ok(s) = obj.downcast::<PyString>() {
+        // Members of str enums may match Starlark enum values.
+        if !obj.is_exact_instance_of::<PyString>() {
+            if let Some(value) = python_enum_to_value(&obj, target, options)? {
+                return Ok(value);
+            }
+        }
+        return Ok(heap.alloc_str(s.to_str()?).to_value());
+    }
+
     if let Some(value) = python_to_decimal(&obj, heap)? {
         return Ok(value);
     }
 
     if let Ok(dict) = obj.downcast::<PyDict>() {
+        let key_options = options.for_keys();
         let mut mp = SmallMap::with_capacity(dict.len());
         for (key, value) in dict.iter() {
-            // Starlark dicts require string keys
-            let key_str: String = key.extract()?;
-            let hashed_key = heap.alloc_str(&key_str).get_hashed_value();
-            let converted = pyobject_to_value(value, heap)?;
+            let hashed_key = match pyobject_to_value(key, target, &key_options)?.get_hashed() {
+                Ok(hashed_key) => hashed_key,
+                Err(e) => return Err(new_err_with_error::<StarlarkConversionError>(e)),
+            };
+            let converted = pyobject_to_value(value, target, options)?;
             mp.insert_hashed(hashed_key, converted);
         }
         return Ok(heap.alloc(Dict::new(mp)));
@@ -218,66 +559,302 @@ fn pyobject_to_value<'v>(obj: Bound<PyAny>, heap: &'v Heap) -> PyResult<Value<'v
     if let Ok(list) = obj.downcast::<PyList>() {
         let elements = list
             .iter()
-            .map(|item| pyobject_to_value(item, heap))
+            .map(|item| pyobject_to_value(item, target, options))
             .collect::<PyResult<Vec<Value<'v>>>>()?;
         return Ok(heap.alloc(AllocList(elements)));
     }
 
     if let Ok(tuple) = obj.downcast::<PyTuple>() {
-        // Convert Python tuples to Starlark lists for backwards compatibility.
-        // Both Python tuples and lists become Starlark lists, and Starlark tuples
-        // also convert to Python lists (matching the old JSON path behavior).
+        // Named tuples become structs.
+        if !obj.is_exact_instance_of::<PyTuple>() {
+            if let Ok(fields) = obj.getattr(intern!(obj.py(), "_fields")) {
+                let names: Vec<String> = fields.extract()?;
+                return alloc_struct(names.into_iter().zip(tuple.iter()), target, options);
+            }
+        }
+
         let elements = tuple
             .iter()
-            .map(|item| pyobject_to_value(item, heap))
+            .map(|item| pyobject_to_value(item, target, options))
             .collect::<PyResult<Vec<Value<'v>>>>()?;
+        if options.preserve_tuples {
+            return Ok(heap.alloc(AllocTuple(elements)));
+        }
+        // Unless asked to preserve them, convert Python tuples to Starlark lists
+        // for backwards compatibility. Both Python tuples and lists become
+        // Starlark lists, and Starlark tuples also convert to Python lists
+        // (matching the old JSON path behavior).
         return Ok(heap.alloc(AllocList(elements)));
     }
 
     if let Ok(opaque) = obj.downcast::<OpaquePythonObject>() {
+        let opaque = opaque.borrow();
+        return Ok(heap.alloc(StarlarkOpaquePythonObject {
+            obj: opaque.obj.clone_ref(obj.py()),
+            members: opaque.members.clone(),
+            fully_opaque: opaque.fully_opaque,
+        }));
+    }
+
+    if let Some(native_type) = native_type_of(&obj, false)? {
         return Ok(heap.alloc(StarlarkOpaquePythonObject {
-            obj: opaque.borrow().0.clone_ref(obj.py()),
+            obj: obj.unbind(),
+            members: native_type.get().members.clone(),
+            fully_opaque: false,
+        }));
+    }
+    if let Some(native_type) = native_type_of(&obj, true)? {
+        let constructor = PythonCallableValue {
+            callable: obj.unbind(),
+            signature: native_type.get().constructor_signature.clone(),
+            pass_context: false,
+        };
+        return Ok(heap.alloc(NativeTypeValue {
+            constructor,
+            native_type: native_type.unbind(),
         }));
     }
 
+    if let Ok(fields) = obj.downcast::<StarlarkStruct>() {
+        return alloc_struct(fields.get().0.bind_items(obj.py()), target, options);
+    }
+    if let Ok(record) = obj.downcast::<StarlarkRecord>() {
+        return alloc_struct(record.get().fields.bind_items(obj.py()), target, options);
+    }
+    if let Ok(callable) = obj.downcast::<StarlarkCallable>() {
+        let Some(module) = target.module() else {
+            return Err(new_err_with_error::<StarlarkConversionError>(
+                starlark::Error::new_other(CallableTargetError),
+            ));
+        };
+        let function = &callable.get().function;
+        if let Some(heaps) = &options.call_heaps {
+            heaps.lock().unwrap().push(function.owner().dupe());
+        }
+        return Ok(function.owned_value(module.frozen_heap()));
+    }
+    if let Ok(enum_value) = obj.downcast::<StarlarkEnumValue>() {
+        let enum_value = enum_value.get();
+        let value = pyobject_to_value(enum_value.value.bind(obj.py()).clone(), target, options)?;
+        return find_enum_value(target, options, &enum_value.enum_type, value)?.ok_or_else(|| {
+            new_err_with_error::<StarlarkConversionError>(starlark::Error::new_other(
+                EnumConversionError::NoEnumType(enum_value.enum_type.clone()),
+            ))
+        });
+    }
+
+    // Dataclass instances become structs.
+    if obj
+        .get_type()
+        .hasattr(intern!(obj.py(), "__dataclass_fields__"))?
+    {
+        let mut fields = Vec::new();
+        let dataclasses = obj.py().import("dataclasses")?;
+        for field in dataclasses.getattr("fields")?.call1((&obj,))?.try_iter()? {
+            let name: String = field?.getattr("name")?.extract()?;
+            let value = obj.getattr(name.as_str())?;
+            fields.push((name, value));
+        }
+        return alloc_struct(fields, target, options);
+    }
+
+    if let Some(value) = python_enum_to_value(&obj, target, options)? {
+        return Ok(value);
+    }
+
     let json = obj.py().import("json")?;
     let json_str: String = json.getattr("dumps")?.call1((obj,))?.extract()?;
-    convert_anyhow_err(serde_to_starlark(
+    convert_conversion_err(serde_to_starlark(
         convert_serde_err(serde_json::from_str(&json_str))?,
         heap,
     ))
 }
 
+fn alloc_struct<'v, 'py>(
+    fields: impl IntoIterator<Item = (String, Bound<'py, PyAny>)>,
+    target: ConversionTarget<'v>,
+    options: &ConversionOptions,
+) -> PyResult<Value<'v>> {
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| Ok((name, pyobject_to_value(value, target, options)?)))
+        .collect::<PyResult<Vec<(String, Value<'v>)>>>()?;
+    Ok(target.heap().alloc(AllocStruct(fields)))
+}
+
 // }}}
 
 // {{{ result conversions
 
+// Creates an exception of type T, making the Starlark error available
+// as its 'error' attribute and its call stack as 'call_stack'.
+fn new_err_with_error<T: PyTypeInfo>(e: starlark::Error) -> PyErr {
+    let msg = e.to_string();
+    let call_stack = call_stack_to_py(e.call_stack());
+    let traceback = starlark_traceback(&e);
+    Python::attach(|py| {
+        let err = PyErr::new::<T, _>(msg);
+        let value = err.value(py);
+        if let Err(setattr_err) = value
+            .setattr("call_stack", call_stack)
+            .and_then(|()| value.setattr("error", Error(e)))
+        {
+            return setattr_err;
+        }
+        match traceback {
+            Some(entries) => add_starlark_traceback(py, err, entries),
+            None => err,
+        }
+    })
+}
+
 fn convert_anyhow_err<T>(err: Result<T, anyhow::Error>) -> Result<T, PyErr> {
     match err {
         Ok(t) => Ok(t),
-        Err(e) => Err(StarlarkError::new_err(e.to_string())),
+        Err(e) => Err(starlark_err_to_pyerr(e.into())),
+    }
+}
+
+fn convert_conversion_err<T>(err: Result<T, anyhow::Error>) -> Result<T, PyErr> {
+    match err {
+        Ok(t) => Ok(t),
+        Err(e) => Err(new_err_with_error::<StarlarkConversionError>(e.into())),
     }
 }
 
 fn convert_starlark_err<T>(err: starlark::Result<T>) -> Result<T, PyErr> {
     match err {
         Ok(t) => Ok(t),
-        Err(e) => Err(StarlarkError::new_err(e.to_string())),
+        Err(e) => Err(starlark_err_to_pyerr(e)),
+    }
+}
+
+// An exception raised by a Python callable invoked from Starlark, kept
+// so that it can be re-raised unchanged once evaluation has failed.
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct PythonCallableError(PyErr);
+
+// Re-raises the exception `err` from a Python callable, attaching the
+// Starlark error `e` as its cause (unless it already has one) and the
+// Starlark call stack as a note.
+fn python_callable_err_to_pyerr(err: PyErr, e: starlark::Error) -> PyErr {
+    let mut note = "Called from Starlark (most recent call last):".to_owned();
+    for frame in &e.call_stack().frames {
+        note.push_str(&format!("\n  {frame}"));
+    }
+    let traceback = starlark_traceback(&e);
+    let cause = new_err_with_error::<StarlarkEvalError>(e);
+    Python::attach(|py| {
+        let value = err.value(py);
+        if err.cause(py).is_none() {
+            err.set_cause(py, Some(cause));
+        }
+        if value.hasattr("add_note").unwrap_or(false) {
+            if let Err(note_err) = value.call_method1("add_note", (note,)) {
+                return note_err;
+            }
+        }
+        match traceback {
+            Some(entries) => add_starlark_traceback(py, err, entries),
+            None => err,
+        }
+    })
+}
+
+// An exception raised in getting or converting the attribute `name` of the
+// opaque object `obj` (by identity).
+struct AttributeError {
+    obj: usize,
+    name: String,
+    err: PyErr,
+}
+
+thread_local! {
+    // starlark-rust offers no way to report errors from getting attributes,
+    // so an exception raised in getting or converting an attribute of an
+    // opaque object is kept here while the attribute appears to be missing.
+    // It is re-raised in place of the resulting error.
+    static ATTRIBUTE_ERROR: RefCell<Option<AttributeError>> = const { RefCell::new(None) };
+}
+
+// The exception raised in getting the attribute that e reports as missing,
+// if any.
+fn take_attribute_error(e: &starlark::Error) -> Option<PyErr> {
+    let starlark::ErrorKind::Value(inner) = e.kind() else {
+        return None;
+    };
+    let (ValueError::NoAttr(type_name, attribute)
+    | ValueError::NoAttrDidYouMean(type_name, attribute, _)) = inner.downcast_ref()?
+    else {
+        return None;
+    };
+    let error = ATTRIBUTE_ERROR.take()?;
+    (type_name == StarlarkOpaquePythonObject::TYPE && error.name == *attribute)
+        .then_some(error.err)
+}
+
+// Whether the value error inner is about values of unsuitable types or
+// unsuitable arguments, rather than, e.g., a missing key or a division
+// by zero.
+fn is_type_error(inner: &anyhow::Error) -> bool {
+    matches!(
+        inner.downcast_ref::<ValueError>(),
+        Some(
+            ValueError::OperationNotSupported { .. }
+                | ValueError::OperationNotSupportedBinary { .. }
+                | ValueError::IncorrectParameterType
+                | ValueError::IncorrectParameterTypeNamed(_)
+                | ValueError::MissingThis
+                | ValueError::MissingRequired(_)
+        )
+    )
+}
+
+fn starlark_err_to_pyerr(e: starlark::Error) -> PyErr {
+    if let starlark::ErrorKind::Other(inner) = e.kind() {
+        if let Some(PythonCallableError(err)) = inner.downcast_ref() {
+            let err = Python::attach(|py| err.clone_ref(py));
+            return python_callable_err_to_pyerr(err, e);
+        }
     }
+    if let Some(err) = take_attribute_error(&e) {
+        return python_callable_err_to_pyerr(err, e);
+    }
+
+    let to_pyerr: fn(starlark::Error) -> PyErr = match e.kind() {
+        starlark::ErrorKind::Parser(_) => new_err_with_error::<StarlarkSyntaxError>,
+        starlark::ErrorKind::Value(inner) if is_type_error(inner) => {
+            new_err_with_error::<StarlarkTypeError>
+        }
+        starlark::ErrorKind::Function(_) => new_err_with_error::<StarlarkTypeError>,
+        starlark::ErrorKind::Freeze(_) => new_err_with_error::<StarlarkFreezeError>,
+        starlark::ErrorKind::StackOverflow(_) => new_err_with_error::<StackOverflowError>,
+        starlark::ErrorKind::Other(inner) => match inner.downcast_ref::<EvalLimitError>() {
+            Some(limit_err) => limit_err.pyerr_constructor(),
+            None => new_err_with_error::<StarlarkEvalError>,
+        },
+        _ => new_err_with_error::<StarlarkEvalError>,
+    };
+    to_pyerr(e)
 }
 
 fn convert_freeze_err<T>(err: FreezeResult<T>) -> Result<T, PyErr> {
     match err {
         Ok(t) => Ok(t),
         // FIXME: Could also format the additional contexts provided.
-        Err(e) => Err(StarlarkError::new_err(e.err_msg)),
+        Err(e) => Err(new_err_with_error::<StarlarkFreezeError>(
+            starlark::Error::new_kind(starlark::ErrorKind::Freeze(anyhow::anyhow!(e.err_msg))),
+        )),
     }
 }
 
 fn convert_serde_err<T>(err: Result<T, serde_json::Error>) -> Result<T, PyErr> {
     match err {
         Ok(t) => Ok(t),
-        Err(e) => Err(StarlarkError::new_err(format!("{}", e))),
+        Err(e) => Err(new_err_with_error::<StarlarkConversionError>(
+            starlark::Error::new_other(e),
+        )),
     }
 }
 
@@ -288,6 +865,205 @@ fn convert_to_starlark_err<T>(err: Result<T, PyErr>) -> Result<T, starlark::Erro
     }
 }
 
+// Like convert_to_starlark_err, except that exceptions raised by Python code
+// run during the conversion (i.e. by converters) are re-raised unchanged, like
+// those of Python callables.
+fn convert_to_starlark_callable_err<T>(err: Result<T, PyErr>) -> Result<T, starlark::Error> {
+    err.map_err(|e| {
+        if Python::attach(|py| e.is_instance_of::<StarlarkError>(py)) {
+            starlark::Error::new_other(e)
+        } else {
+            starlark::Error::new_other(PythonCallableError(e))
+        }
+    })
+}
+
+// }}}
+
+// {{{ evaluation limits
+
+#[derive(Debug, Error)]
+enum EvalLimitError {
+    #[error("Evaluation exceeded the limit of {0} steps")]
+    StepLimitExceeded(u64),
+    #[error("Evaluation timed out after {0:?}")]
+    TimedOut(Duration),
+    #[error("Evaluation was cancelled")]
+    Cancelled,
+    #[error("Evaluation exceeded the heap limit of {0} bytes")]
+    MemoryLimitExceeded(usize),
+}
+
+impl EvalLimitError {
+    fn pyerr_constructor(&self) -> fn(starlark::Error) -> PyErr {
+        match self {
+            EvalLimitError::StepLimitExceeded(_) => new_err_with_error::<StepLimitExceeded>,
+            EvalLimitError::TimedOut(_) | EvalLimitError::Cancelled => {
+                new_err_with_error::<EvaluationCancelled>
+            }
+            EvalLimitError::MemoryLimitExceeded(_) => new_err_with_error::<MemoryLimitExceeded>,
+        }
+    }
+}
+
+/// Resource limits applied to a single run of the evaluator, as requested
+/// by the keyword arguments of :func:`eval` and :meth:`FrozenModule.call_with`.
+#[derive(Clone, Default)]
+struct EvalLimits {
+    max_steps: Option<u64>,
+    timeout: Option<(Duration, Instant)>,
+    cancelled: Option<Arc<AtomicBool>>,
+    max_heap_bytes: Option<usize>,
+    max_callstack_depth: Option<usize>,
+}
+
+impl EvalLimits {
+    fn new(
+        max_steps: Option<u64>,
+        timeout: Option<f64>,
+        cancellation_token: Option<&CancellationToken>,
+        max_heap_bytes: Option<usize>,
+        max_callstack_depth: Option<usize>,
+    ) -> PyResult<EvalLimits> {
+        if max_callstack_depth == Some(0) {
+            return Err(PyValueError::new_err("max_callstack_depth must be positive"));
+        }
+        let timeout = match timeout {
+            Some(secs) => {
+                let duration = Duration::try_from_secs_f64(secs)
+                    .map_err(|e| PyValueError::new_err(format!("invalid timeout: {e}")))?;
+                // The clock starts ticking when the evaluation is requested.
+                Some((duration, Instant::now() + duration))
+            }
+            None => None,
+        };
+        Ok(EvalLimits {
+            max_steps,
+            timeout,
+            cancelled: cancellation_token.map(|token| token.0.dupe()),
+            max_heap_bytes,
+            max_callstack_depth,
+        })
+    }
+
+    fn needs_stmt_hook(&self) -> bool {
+        self.max_steps.is_some()
+            || self.timeout.is_some()
+            || self.cancelled.is_some()
+            || self.max_heap_bytes.is_some()
+    }
+
+    fn install(&self, evaluator: &mut Evaluator) -> PyResult<()> {
+        if let Some(depth) = self.max_callstack_depth {
+            // The evaluator occupies one frame of its own for the module
+            // (or function) being evaluated.
+            convert_anyhow_err(evaluator.set_max_callstack_size(depth + 1))?;
+        }
+        // Instrumenting statements has a runtime cost, only pay it if needed.
+        if !self.needs_stmt_hook() {
+            return Ok(());
+        }
+        // NOTE: starlark-rust documents this hook as being for the debug
+        // adapter, but it is the only way to run code before each statement.
+        let hook: Box<dyn BeforeStmtFuncDyn> = Box::new(EvalLimitsHook {
+            limits: self.clone(),
+            steps: 0,
+        });
+        evaluator.before_stmt_for_dap(hook.into());
+        Ok(())
+    }
+
+    // The heap is checked before each statement and again once the
+    // evaluation returns, since allocations by the last statement would
+    // otherwise go unnoticed.
+    fn check_heap(&self, heap: &Heap) -> starlark::Result<()> {
+        if let Some(max_heap_bytes) = self.max_heap_bytes {
+            if heap.allocated_bytes() > max_heap_bytes {
+                return Err(starlark::Error::new_other(
+                    EvalLimitError::MemoryLimitExceeded(max_heap_bytes),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct EvalLimitsHook {
+    limits: EvalLimits,
+    steps: u64,
+}
+
+impl<'a, 'e: 'a> BeforeStmtFuncDyn<'a, 'e> for EvalLimitsHook {
+    fn call<'v>(
+        &mut self,
+        _span: FileSpanRef,
+        eval: &mut Evaluator<'v, 'a, 'e>,
+    ) -> starlark::Result<()> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(starlark::Error::new_other(
+                    EvalLimitError::StepLimitExceeded(max_steps),
+                ));
+            }
+        }
+        if let Some((duration, deadline)) = self.limits.timeout {
+            if Instant::now() >= deadline {
+                return Err(starlark::Error::new_other(EvalLimitError::TimedOut(
+                    duration,
+                )));
+            }
+        }
+        if let Some(cancelled) = &self.limits.cancelled {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(starlark::Error::new_other(EvalLimitError::Cancelled));
+            }
+        }
+        self.limits.check_heap(eval.heap())
+    }
+}
+
+// }}}
+
+// {{{ CancellationToken
+
+/// A flag that may be set from any thread to stop evaluations it was
+/// passed to. :func:`eval` releases the GIL while it runs, so another
+/// Python thread may call :meth:`cancel` on a token used by a running
+/// evaluation, which then fails with :exc:`EvaluationCancelled`.
+///
+/// Cancellation is checked before each Starlark statement. It cannot
+/// interrupt a statement that is already running, such as a long-running
+/// builtin or Python callable, which runs to completion first.
+///
+/// .. automethod:: cancel
+/// .. autoattribute:: cancelled
+///
+///     A :class:`bool`.
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct CancellationToken(Arc<AtomicBool>);
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    #[pyo3(text_signature = "() -> None")]
+    fn py_new() -> CancellationToken {
+        CancellationToken(Arc::new(AtomicBool::new(false)))
+    }
+
+    #[pyo3(text_signature = "() -> None")]
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 // }}}
 
 // {{{ ResolvedPos
@@ -369,6 +1145,89 @@ impl ResolvedFileSpan {
 
 // }}}
 
+// {{{ CallFrame
+
+/// An entry of a Starlark call stack.
+///
+/// .. autoattribute:: name
+///
+///     A :class:`str`, the name of the called function.
+/// .. autoattribute:: location
+///
+///     A :class:`ResolvedFileSpan` of the call site, or *None*
+///     if unavailable.
+/// .. automethod:: __str__
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct CallFrame(starlark::errors::Frame);
+
+#[pymethods]
+impl CallFrame {
+    #[getter]
+    fn name(&self) -> String {
+        self.0.name.clone()
+    }
+    #[getter]
+    fn location(&self) -> Option<ResolvedFileSpan> {
+        self.0
+            .location
+            .as_ref()
+            .map(|loc| ResolvedFileSpan(loc.resolve()))
+    }
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+fn call_stack_to_py(call_stack: &starlark::eval::CallStack) -> Vec<CallFrame> {
+    call_stack.frames.iter().cloned().map(CallFrame).collect()
+}
+
+// }}}
+
+// {{{ CallContext
+
+/// The context of a call from Starlark to a Python callable added with
+/// *pass_context* (see :meth:`Module.add_callable`).
+///
+/// .. autoattribute:: location
+///
+///     A :class:`ResolvedFileSpan` of the call site, or *None*
+///     if unavailable.
+/// .. autoattribute:: call_stack
+///
+///     The Starlark call stack, as a list of :class:`CallFrame`,
+///     outermost call first.
+/// .. autoattribute:: module_name
+///
+///     The :attr:`Module.name` of the module being evaluated, or *None*.
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct CallContext {
+    location: Option<starlark::codemap::FileSpan>,
+    call_stack: starlark::eval::CallStack,
+    #[pyo3(get)]
+    module_name: Option<String>,
+}
+
+#[pymethods]
+impl CallContext {
+    #[getter]
+    fn location(&self) -> Option<ResolvedFileSpan> {
+        self.location
+            .as_ref()
+            .map(|loc| ResolvedFileSpan(loc.resolve()))
+    }
+    #[getter]
+    fn call_stack(&self) -> Vec<CallFrame> {
+        call_stack_to_py(&self.call_stack)
+    }
+}
+
+// }}}
+
 // {{{ EvalSeverity
 
 /// .. attribute:: Error
@@ -477,6 +1336,9 @@ impl Lint {
 
 /// .. attribute:: span: ResolvedFileSpan | None
 /// .. automethod:: __str__
+///
+/// Exceptions raised by this module that derive from :exc:`StarlarkError`
+/// carry an instance of this type as their ``error`` attribute.
 #[pyclass]
 struct Error(starlark::Error);
 
@@ -484,10 +1346,7 @@ struct Error(starlark::Error);
 impl Error {
     #[getter]
     fn span(&self) -> Option<ResolvedFileSpan> {
-        match self.0.span() {
-            Some(span) => Some(ResolvedFileSpan(span.resolve())),
-            None => None,
-        }
+        self.0.span().map(|span| ResolvedFileSpan(span.resolve()))
     }
     fn __str__(&self) -> String {
         self.0.to_string()
@@ -707,28 +1566,70 @@ impl AstModule {
             .collect()
     }
 
+    /// Typecheck the module against *globals* and the interfaces of loaded
+    /// modules in *loads*. If *module* is given, the callables added to it
+    /// with :meth:`Module.add_callable` are known to the typechecker, with
+    /// their signatures.
+    ///
+    /// .. versionchanged:: 2025.2.6
+    ///
+    ///     Added *module*.
     #[pyo3(
-        text_signature = "(globals: Globals, loads: dict[str, Interface]) -> tuple[list[Error], Interface, tuple[Never, ...]]"
+        signature = (globals, py_loads, *, module=None),
+        text_signature = "(globals: Globals, loads: dict[str, Interface], *, module: Module | None = None) -> tuple[list[Error], Interface, tuple[Never, ...]]"
     )]
     fn typecheck<'py>(
         slf: Bound<'py, AstModule>,
         globals: &Globals,
         py_loads: HashMap<String, Bound<'py, Interface>>,
-    ) -> (Vec<Error>, Interface, ()) {
+        module: Option<Bound<'py, Module>>,
+    ) -> PyResult<(Vec<Error>, Interface, ())> {
         // FIXME: Can we get by without cloning all the interfaces?
         let loads: HashMap<String, starlark::typing::Interface> = py_loads
             .iter()
             .map(|(name, iface)| (name.clone(), iface.get().0.clone()))
             .collect();
+        let module_globals;
+        let globals = match &module {
+            Some(module) => {
+                module_globals = globals_with_callables(&globals.0, module)?;
+                &module_globals
+            }
+            None => &globals.0,
+        };
         // FIXME: Can we make do without cloning the module?
         let (mut errors, _typemap, iface, _approximations) =
-            slf.borrow().0.clone().typecheck(&globals.0, &loads);
-        (
+            slf.borrow().0.clone().typecheck(globals, &loads);
+        Ok((
             errors.drain(..).map(|err| Error(err)).collect(),
             Interface(iface),
             (),
-        )
+        ))
+    }
+}
+
+// Extend globals by the Python callables of module, for the typechecker.
+fn globals_with_callables(
+    globals: &starlark::environment::Globals,
+    module: &Bound<Module>,
+) -> PyResult<starlark::environment::Globals> {
+    let mut builder = GlobalsBuilder::new();
+    // The values of globals live on its heap, which is kept alive by globals.
+    builder.frozen_heap().alloc_any(globals.dupe());
+    for (name, value) in globals.iter() {
+        builder.set(name, value);
+    }
+    let module_ref = module.borrow();
+    let module_locked = module_ref.lock(module.py())?;
+    for name in module_locked.names() {
+        let callable = module_locked
+            .get(name.as_str())
+            .and_then(|value| value.downcast_ref::<PythonCallableValue>());
+        if let Some(callable) = callable {
+            builder.set(name.as_str(), callable.clone_ref(module.py()));
+        }
     }
+    Ok(builder.build())
 }
 
 // }}}
@@ -864,6 +1765,7 @@ impl LibraryExtension {
 
 /// .. automethod:: standard
 /// .. automethod:: extended_by
+/// .. automethod:: builder
 #[pyclass]
 struct Globals(starlark::environment::Globals);
 
@@ -879,15 +1781,325 @@ impl Globals {
     #[pyo3(text_signature = "(extensions: list[LibraryExtension]) -> Globals")]
     fn extended_by(extensions: Vec<LibraryExtension>) -> PyResult<Globals> {
         let mut builder = GlobalsBuilder::standard();
+        add_extensions(&mut builder, &extensions);
+        Ok(Globals(builder.build()))
+    }
+
+    /// Return a :class:`GlobalsBuilder` starting from the standard globals,
+    /// like :meth:`GlobalsBuilder.standard`.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[staticmethod]
+    #[pyo3(text_signature = "() -> GlobalsBuilder")]
+    fn builder() -> PyGlobalsBuilder {
+        PyGlobalsBuilder::standard()
+    }
+}
 
-        for ext in &extensions {
-            match &ext.kind {
-                LibraryExtensionKind::Upstream(upstream) => upstream.add(&mut builder),
-                LibraryExtensionKind::RustDecimal => decimal_module(&mut builder),
+fn add_extensions(builder: &mut GlobalsBuilder, extensions: &[LibraryExtension]) {
+    for ext in extensions {
+        match &ext.kind {
+            LibraryExtensionKind::Upstream(upstream) => upstream.add(builder),
+            LibraryExtensionKind::RustDecimal => decimal_module(builder),
+        }
+    }
+}
+
+// }}}
+
+// {{{ GlobalsBuilder
+
+// A global added to a GlobalsBuilder, kept until the Globals are built.
+enum GlobalsEntry {
+    Value(OwnedFrozenValue),
+    Callable(PythonCallableValue),
+    Namespace(Vec<(String, GlobalsEntry)>),
+}
+
+fn add_globals_entries(
+    py: Python<'_>,
+    builder: &mut GlobalsBuilder,
+    entries: &[(String, GlobalsEntry)],
+) {
+    for (name, entry) in entries {
+        match entry {
+            GlobalsEntry::Value(value) => builder.set(name, value.dupe()),
+            GlobalsEntry::Callable(callable) => builder.set(name, callable.clone_ref(py)),
+            GlobalsEntry::Namespace(entries) => {
+                builder.namespace(name, |builder| add_globals_entries(py, builder, entries))
             }
         }
+    }
+}
 
-        Ok(Globals(builder.build()))
+// Converts obj to a frozen value, which is kept alive by the frozen heap of
+// a module made for this purpose.
+fn pyobject_to_frozen_value(obj: Bound<PyAny>) -> PyResult<OwnedFrozenValue> {
+    let module = starlark::environment::Module::new();
+    let options = ConversionOptions::new(None, None, None);
+    module.set(
+        "value",
+        pyobject_to_value(obj, ConversionTarget::Module(&module), &options)?,
+    );
+    convert_anyhow_err(convert_freeze_err(module.freeze())?.get("value"))
+}
+
+fn namespace_entry(member: Bound<PyAny>) -> PyResult<GlobalsEntry> {
+    if member.is_callable() && native_type_of(&member, true)?.is_none() {
+        Ok(GlobalsEntry::Callable(PythonCallableValue::new(
+            member, None, false,
+        )?))
+    } else {
+        Ok(GlobalsEntry::Value(pyobject_to_frozen_value(member)?))
+    }
+}
+
+fn namespace_entries(members: &Bound<PyAny>) -> PyResult<Vec<(String, GlobalsEntry)>> {
+    let py = members.py();
+    let mut entries = Vec::new();
+    if !members.is_instance_of::<PyModule>() {
+        for item in members.call_method0(intern!(py, "items"))?.try_iter()? {
+            let (name, member): (String, Bound<PyAny>) = item?.extract()?;
+            entries.push((name, namespace_entry(member)?));
+        }
+        return Ok(entries);
+    }
+
+    if let Some(names) = members.getattr_opt(intern!(py, "__all__"))? {
+        for name in names.extract::<Vec<String>>()? {
+            let member = members.getattr(name.as_str())?;
+            entries.push((name, namespace_entry(member)?));
+        }
+        return Ok(entries);
+    }
+    let module_name = members.getattr(intern!(py, "__name__"))?;
+    for name in members.dir()?.extract::<Vec<String>>()? {
+        if name.starts_with('_') {
+            continue;
+        }
+        let member = members.getattr(name.as_str())?;
+        if member.is_instance_of::<PyModule>() {
+            continue;
+        }
+        // Skip members imported from elsewhere, such as helpers of other
+        // modules. Plain values like numbers and strings have no __module__.
+        if let Some(owner) = member.getattr_opt(intern!(py, "__module__"))? {
+            if !owner.eq(&module_name)? {
+                continue;
+            }
+        }
+        match namespace_entry(member) {
+            Ok(entry) => entries.push((name, entry)),
+            Err(err) if err.is_instance_of::<StarlarkConversionError>(py) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(entries)
+}
+
+/// Builds :class:`Globals` including Python callables and values, which
+/// are then available to all modules evaluated with these globals,
+/// including loaded ones, and known to :meth:`AstModule.typecheck`.
+///
+/// Values are converted to Starlark (see :ref:`object-conversion`) when
+/// they are added, using the global defaults for conversion.
+///
+/// .. automethod:: standard
+/// .. automethod:: extended_by
+/// .. automethod:: add_callable
+/// .. automethod:: set
+/// .. automethod:: add_namespace
+/// .. automethod:: without
+/// .. automethod:: only
+/// .. automethod:: build
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(name = "GlobalsBuilder")]
+struct PyGlobalsBuilder {
+    // The library extensions to start from, or None to start from an
+    // empty builder.
+    extensions: Option<Vec<LibraryExtension>>,
+    // Library globals that are left out, and those that are kept, if
+    // restricted to a set.
+    hidden: Vec<String>,
+    allowed: Option<Vec<String>>,
+    entries: Vec<(String, GlobalsEntry)>,
+}
+
+impl PyGlobalsBuilder {
+    fn with_extensions(extensions: Option<Vec<LibraryExtension>>) -> PyGlobalsBuilder {
+        PyGlobalsBuilder {
+            extensions,
+            hidden: Vec::new(),
+            allowed: None,
+            entries: Vec::new(),
+        }
+    }
+
+    // The standard globals and those of the extensions.
+    fn library_builder(&self) -> GlobalsBuilder {
+        match &self.extensions {
+            Some(extensions) => {
+                let mut builder = GlobalsBuilder::standard();
+                add_extensions(&mut builder, extensions);
+                builder
+            }
+            None => GlobalsBuilder::new(),
+        }
+    }
+
+    fn check_library_names(&self, names: &[String]) -> PyResult<()> {
+        let library = self.library_builder().build();
+        for name in names {
+            if !library.names().any(|known| known.as_str() == name) {
+                return Err(PyValueError::new_err(format!("Unknown global `{name}`")));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_hidden(&self, name: &str) -> bool {
+        self.hidden.iter().any(|hidden| hidden == name)
+            || self
+                .allowed
+                .as_ref()
+                .is_some_and(|allowed| !allowed.iter().any(|allowed| allowed == name))
+    }
+}
+
+#[pymethods]
+impl PyGlobalsBuilder {
+    /// Create a builder without any globals, not even the standard ones.
+    #[new]
+    #[pyo3(text_signature = "() -> None")]
+    fn py_new() -> PyGlobalsBuilder {
+        PyGlobalsBuilder::with_extensions(None)
+    }
+
+    /// Create a builder starting from the globals of :meth:`Globals.standard`.
+    #[staticmethod]
+    #[pyo3(text_signature = "() -> GlobalsBuilder")]
+    fn standard() -> PyGlobalsBuilder {
+        PyGlobalsBuilder::with_extensions(Some(Vec::new()))
+    }
+
+    /// Create a builder starting from the globals of
+    /// :meth:`Globals.extended_by`.
+    #[staticmethod]
+    #[pyo3(text_signature = "(extensions: list[LibraryExtension]) -> GlobalsBuilder")]
+    fn extended_by(extensions: Vec<LibraryExtension>) -> PyGlobalsBuilder {
+        PyGlobalsBuilder::with_extensions(Some(extensions))
+    }
+
+    /// Make the Python *callable* available as *name*, like
+    /// :meth:`Module.add_callable`.
+    #[pyo3(
+        signature = (name, callable, *, signature=None, pass_context=false),
+        text_signature = "(name: str, callable: Callable, *, signature: inspect.Signature | None = None, pass_context: bool = False) -> None"
+    )]
+    fn add_callable(
+        &mut self,
+        name: String,
+        callable: Bound<PyAny>,
+        signature: Option<Bound<PyAny>>,
+        pass_context: bool,
+    ) -> PyResult<()> {
+        let callable = PythonCallableValue::new(callable, signature, pass_context)?;
+        self.entries.push((name, GlobalsEntry::Callable(callable)));
+        Ok(())
+    }
+
+    /// Make *value* available as *name*.
+    #[pyo3(text_signature = "(name: str, value: object) -> None")]
+    fn set(&mut self, name: String, value: Bound<PyAny>) -> PyResult<()> {
+        let value = pyobject_to_frozen_value(value)?;
+        self.entries.push((name, GlobalsEntry::Value(value)));
+        Ok(())
+    }
+
+    /// Make the entries of *members* available as attributes of the
+    /// namespace *name*, e.g. as ``fs.read_text()``. Callable entries are
+    /// added as with :meth:`add_callable`, and other entries as with
+    /// :meth:`set`.
+    ///
+    /// *members* is a mapping or a Python module. Of a module, the members
+    /// named in its ``__all__`` are used, or else its public members that
+    /// are defined in the module itself, as told by their ``__module__``.
+    /// Modules and values raising :exc:`StarlarkConversionError` are
+    /// skipped then, while other errors in converting a member are raised.
+    ///
+    /// A dotted *name* such as ``"net.dns"`` adds a namespace within a
+    /// namespace. Adding to an existing namespace keeps its other members.
+    #[pyo3(text_signature = "(name: str, members: Mapping[str, object] | ModuleType) -> None")]
+    fn add_namespace(&mut self, name: &str, members: Bound<PyAny>) -> PyResult<()> {
+        let mut entries = namespace_entries(&members)?;
+        let mut namespace = &mut self.entries;
+        for part in name.split('.') {
+            let index = namespace
+                .iter()
+                .position(|(name, entry)| {
+                    name == part && matches!(entry, GlobalsEntry::Namespace(_))
+                })
+                .unwrap_or_else(|| {
+                    namespace.push((part.to_owned(), GlobalsEntry::Namespace(Vec::new())));
+                    namespace.len() - 1
+                });
+            let GlobalsEntry::Namespace(inner) = &mut namespace[index].1 else {
+                unreachable!("found a namespace");
+            };
+            namespace = inner;
+        }
+        namespace.append(&mut entries);
+        Ok(())
+    }
+
+    /// Leave out the standard globals and those of library extensions
+    /// named in *names*, such as ``print`` or ``getattr``. Globals added
+    /// to the builder are not affected.
+    ///
+    /// :returns: the builder, so that calls may be chained.
+    /// :raises ValueError: if one of *names* is not such a global.
+    #[pyo3(text_signature = "(names: Sequence[str]) -> GlobalsBuilder")]
+    fn without(mut slf: PyRefMut<'_, Self>, names: Vec<String>) -> PyResult<PyRefMut<'_, Self>> {
+        slf.check_library_names(&names)?;
+        slf.hidden.extend(names);
+        Ok(slf)
+    }
+
+    /// Leave out the standard globals and those of library extensions
+    /// except those named in *names*, replacing a previous allowlist.
+    /// Globals added to the builder are not affected.
+    ///
+    /// :returns: the builder, so that calls may be chained.
+    /// :raises ValueError: if one of *names* is not such a global.
+    #[pyo3(text_signature = "(names: Sequence[str]) -> GlobalsBuilder")]
+    fn only(mut slf: PyRefMut<'_, Self>, names: Vec<String>) -> PyResult<PyRefMut<'_, Self>> {
+        slf.check_library_names(&names)?;
+        slf.allowed = Some(names);
+        Ok(slf)
+    }
+
+    /// Return :class:`Globals` with the globals added so far. The builder
+    /// may be used further afterwards.
+    #[pyo3(text_signature = "() -> Globals")]
+    fn build(&self, py: Python<'_>) -> Globals {
+        let mut builder = if self.hidden.is_empty() && self.allowed.is_none() {
+            self.library_builder()
+        } else {
+            let library = self.library_builder().build();
+            let mut builder = GlobalsBuilder::new();
+            // The values of the library live on its heap, which is kept
+            // alive by the library.
+            builder.frozen_heap().alloc_any(library.dupe());
+            for (name, value) in library.iter() {
+                if !self.is_hidden(name) {
+                    builder.set(name, value);
+                }
+            }
+            builder
+        };
+        add_globals_entries(py, &mut builder, &self.entries);
+        Globals(builder.build())
     }
 }
 
@@ -895,160 +2107,1340 @@ impl Globals {
 
 // {{{ OpaquePythonObject
 
+// The members of an opaque object that Starlark may access.
+#[derive(Debug, Default)]
+struct OpaqueMembers {
+    attrs: Vec<String>,
+    methods: Vec<String>,
+    // Signatures of methods, where known.
+    signatures: HashMap<String, Arc<Signature>>,
+    // For objects of a native type, the id and type of the native type.
+    native_type: Option<(TypeInstanceId, Ty)>,
+}
+
 #[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
 struct StarlarkOpaquePythonObject {
     #[allocative(skip)]
     obj: Py<PyAny>,
+    #[allocative(skip)]
+    members: Arc<OpaqueMembers>,
+    fully_opaque: bool,
 }
 starlark_simple_value!(StarlarkOpaquePythonObject);
 
 impl Display for StarlarkOpaquePythonObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.fully_opaque {
+            if let Ok(repr) = Python::attach(|py| self.obj.bind(py).repr()?.extract::<String>()) {
+                return write!(f, "{repr}");
+            }
+        }
         write!(f, "<opaque python object>")
     }
 }
 
 #[starlark_value(type = "opaque_python_object")]
-impl<'v> StarlarkValue<'v> for StarlarkOpaquePythonObject {}
+impl<'v> StarlarkValue<'v> for StarlarkOpaquePythonObject {
+    fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        ATTRIBUTE_ERROR.set(None);
+        let is_method = self.members.methods.iter().any(|name| name == attribute);
+        if !is_method && !self.members.attrs.iter().any(|name| name == attribute) {
+            return None;
+        }
+        Python::attach(|py| {
+            let member = self.obj.bind(py).getattr(attribute).and_then(|member| {
+                if is_method {
+                    return Ok(heap.alloc(PythonCallableValue {
+                        callable: member.unbind(),
+                        signature: self.members.signatures.get(attribute).cloned(),
+                        pass_context: false,
+                    }));
+                }
+                let options = ConversionOptions::current();
+                pyobject_to_value(member, ConversionTarget::Heap(heap), &options)
+            });
+            match member {
+                Ok(member) => Some(member),
+                Err(err) => {
+                    ATTRIBUTE_ERROR.set(Some(AttributeError {
+                        obj: self.obj.as_ptr() as usize,
+                        name: attribute.to_owned(),
+                        err,
+                    }));
+                    None
+                }
+            }
+        })
+    }
+
+    fn has_attr(&self, attribute: &str, heap: &'v Heap) -> bool {
+        let found = self.get_attr(attribute, heap).is_some();
+        // hasattr() reports a failing attribute as missing, so its error
+        // must not be re-raised later.
+        ATTRIBUTE_ERROR.with_borrow_mut(|error| {
+            if error
+                .as_ref()
+                .is_some_and(|e| e.obj == self.obj.as_ptr() as usize && e.name == attribute)
+            {
+                *error = None;
+            }
+        });
+        found
+    }
+
+    fn dir_attr(&self) -> Vec<String> {
+        let mut names = self.members.attrs.clone();
+        names.extend(self.members.methods.iter().cloned());
+        names
+    }
+
+    fn equals(&self, other: Value<'v>) -> starlark::Result<bool> {
+        let Some(other) = other.downcast_ref::<StarlarkOpaquePythonObject>() else {
+            return Ok(false);
+        };
+        // Fully opaque objects hash by identity, the others by __hash__, so
+        // the two kinds must not be equal to each other.
+        if self.fully_opaque != other.fully_opaque {
+            return Ok(false);
+        }
+        if self.fully_opaque {
+            return Ok(self.obj.is(&other.obj));
+        }
+        Python::attach(|py| self.obj.bind(py).eq(&other.obj))
+            .map_err(|e| starlark::Error::new_other(PythonCallableError(e)))
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> starlark::Result<()> {
+        if self.fully_opaque {
+            self.obj.as_ptr().hash(hasher);
+            return Ok(());
+        }
+        let hash = Python::attach(|py| self.obj.bind(py).hash())
+            .map_err(|e| starlark::Error::new_other(PythonCallableError(e)))?;
+        hash.hash(hasher);
+        Ok(())
+    }
+
+    fn typechecker_ty(&self) -> Option<Ty> {
+        let (_, ty) = self.members.native_type.as_ref()?;
+        Some(ty.clone())
+    }
+}
 
-/// An 'opaque' Python object that can be passed to Starlark. It cannot be
-/// interacted with from the Starlark side. Upon conversion from Starlark
-/// to Python, the original wrapped object reappears.
+/// An 'opaque' Python object that can be passed to Starlark. Upon
+/// conversion from Starlark to Python, the original wrapped object
+/// reappears.
+///
+/// By default, it cannot be interacted with from the Starlark side. The
+/// attributes of *obj* named in *attrs* may be read from Starlark, with
+/// their values converted as described in :ref:`object-conversion`, and
+/// the methods named in *methods* may be called. Exceptions raised in
+/// getting or converting an attribute, or by a method, propagate like
+/// those of callables added with :meth:`Module.add_callable`.
+///
+/// Comparison, hashing and :func:`repr` in Starlark use those of *obj*,
+/// unless *fully_opaque* is *True*, in which case objects are only equal
+/// if they wrap the same *obj*. Fully opaque objects are never equal to
+/// ones that are not.
 ///
 /// .. versionadded:: 2025.2.5
+/// .. versionchanged:: 2025.2.6
+///
+///     Added *attrs*, *methods* and *fully_opaque*. Added comparison,
+///     hashing and :func:`repr`.
 #[pyclass]
-struct OpaquePythonObject(Py<PyAny>);
+struct OpaquePythonObject {
+    obj: Py<PyAny>,
+    members: Arc<OpaqueMembers>,
+    fully_opaque: bool,
+}
 
 #[pymethods]
 impl OpaquePythonObject {
     #[new]
-    #[pyo3(signature = (obj))]
-    fn new(obj: Py<PyAny>) -> Self {
-        OpaquePythonObject(obj)
+    #[pyo3(
+        signature = (obj, *, attrs=Vec::new(), methods=Vec::new(), fully_opaque=false),
+        text_signature = "(obj: object, *, attrs: Sequence[str] = (), methods: Sequence[str] = (), fully_opaque: bool = False) -> None"
+    )]
+    fn new(obj: Py<PyAny>, attrs: Vec<String>, methods: Vec<String>, fully_opaque: bool) -> Self {
+        OpaquePythonObject {
+            obj,
+            members: Arc::new(OpaqueMembers {
+                attrs,
+                methods,
+                ..OpaqueMembers::default()
+            }),
+            fully_opaque,
+        }
     }
 }
 
 // }}}
 
-// {{{ PythonCallableValue
+// {{{ NativeType
+
+// Matches the objects of a native type, e.g. for type annotations.
+#[derive(Debug, Clone, Allocative)]
+struct NativeTypeMatcher {
+    id: TypeInstanceId,
+}
+
+impl TypeMatcher for NativeTypeMatcher {
+    fn matches(&self, value: Value) -> bool {
+        value
+            .downcast_ref::<StarlarkOpaquePythonObject>()
+            .and_then(|obj| obj.members.native_type.as_ref())
+            .is_some_and(|(id, _)| *id == self.id)
+    }
+}
+
+/// The Starlark type of the objects of a class decorated with
+/// :func:`native_type`, available as the ``__starlark_type__`` attribute
+/// of the class.
+///
+/// .. attribute:: name
+///
+///     The name of the type, as used in Starlark.
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct NativeType {
+    #[pyo3(get)]
+    name: String,
+    members: Arc<OpaqueMembers>,
+    // The type of the class itself, which constructs objects when called.
+    class_ty: Ty,
+    constructor_signature: Option<Arc<Signature>>,
+}
+
+fn convert_ty_err<T, E: Display>(result: Result<T, E>) -> PyResult<T> {
+    result.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+impl NativeType {
+    fn new(
+        cls: &Bound<PyType>,
+        name: Option<String>,
+        attrs: &[String],
+        methods: &[String],
+    ) -> PyResult<NativeType> {
+        let py = cls.py();
+        let name = match name {
+            Some(name) => name,
+            None => cls.name()?.to_string(),
+        };
+
+        let mut fields: Vec<(String, Ty)> = attrs
+            .iter()
+            .cloned()
+            .zip(signature::attribute_types(cls.as_any(), attrs)?)
+            .collect();
+        let mut signatures = HashMap::new();
+        let inspect = py.import("inspect")?;
+        for method in methods {
+            // Methods are called bound to an object, so self is not passed
+            // from Starlark, unlike for static and class methods.
+            let is_function = inspect
+                .getattr("isfunction")?
+                .call1((inspect.getattr("getattr_static")?.call1((cls, method))?,))?
+                .is_truthy()?;
+            let signature = Signature::from_python(&cls.getattr(method)?, None, is_function)?;
+            let ty = signature.as_ref().map_or_else(Ty::any, |s| s.ty().clone());
+            fields.push((method.clone(), ty));
+            if let Some(signature) = signature {
+                signatures.insert(method.clone(), Arc::new(signature));
+            }
+        }
+
+        let id = TypeInstanceId::gen();
+        let ty = Ty::custom(convert_ty_err(TyUser::new(
+            name.clone(),
+            TyStarlarkValue::new::<StarlarkOpaquePythonObject>(),
+            id,
+            TyUserParams {
+                matcher: Some(TypeMatcherFactory::new(NativeTypeMatcher { id })),
+                fields: TyUserFields {
+                    known: fields.into_iter().collect(),
+                    unknown: false,
+                },
+                ..TyUserParams::default()
+            },
+        ))?);
+
+        let constructor_signature = Signature::from_python(cls.as_any(), None, false)?.map(Arc::new);
+        let params = match &constructor_signature {
+            Some(signature) => signature.param_spec().clone(),
+            None => convert_ty_err(ParamSpec::new_parts(
+                [],
+                [],
+                Some(Ty::any()),
+                [],
+                Some(Ty::any()),
+            ))?,
+        };
+        let class_ty = Ty::custom(convert_ty_err(TyUser::new(
+            format!("type[{name}]"),
+            TyStarlarkValue::new::<NativeTypeValue>(),
+            TypeInstanceId::gen(),
+            TyUserParams {
+                callable: Some(TyCallable::new(params, ty.clone())),
+                ..TyUserParams::default()
+            },
+        ))?);
+
+        Ok(NativeType {
+            name,
+            members: Arc::new(OpaqueMembers {
+                attrs: attrs.to_vec(),
+                methods: methods.to_vec(),
+                signatures,
+                native_type: Some((id, ty)),
+            }),
+            class_ty,
+            constructor_signature,
+        })
+    }
+}
 
+// A class decorated with native_type, which may be called to construct
+// objects and used in type annotations.
 #[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
-struct PythonCallableValue {
+struct NativeTypeValue {
+    constructor: PythonCallableValue,
     #[allocative(skip)]
-    callable: Py<PyAny>,
+    native_type: Py<NativeType>,
 }
-starlark_simple_value!(PythonCallableValue);
+starlark_simple_value!(NativeTypeValue);
 
-impl Display for PythonCallableValue {
+impl Display for NativeTypeValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "<python callable>")
+        write!(f, "{}", self.native_type.get().name)
     }
 }
 
-#[starlark_value(type = "python_callable_value")]
-impl<'v> StarlarkValue<'v> for PythonCallableValue {
+#[starlark_value(type = "native_type")]
+impl<'v> StarlarkValue<'v> for NativeTypeValue {
     fn invoke(
         &self,
-        _me: Value<'v>,
+        me: Value<'v>,
         args: &Arguments<'v, '_>,
         eval: &mut starlark::eval::Evaluator<'v, '_, '_>,
     ) -> starlark::Result<Value<'v>> {
-        Python::attach(|py| -> starlark::Result<Value<'v>> {
-            // Handle positional arguments
-            let py_args: Vec<Py<PyAny>> = convert_to_starlark_err(
-                (args
-                    .positions(eval.heap())?
-                    .map(|v| -> PyResult<Py<PyAny>> { value_to_pyobject(v) }))
-                .collect::<PyResult<Vec<Py<PyAny>>>>(),
-            )?;
-            let py_args_tuple = convert_to_starlark_err(PyTuple::new(py, py_args))?;
+        self.constructor.invoke(me, args, eval)
+    }
 
-            // Handle named arguments
-            let py_kwargs = PyDict::new(py);
-            for name in args.names_map()?.iter() {
-                let key = name.0.as_str();
-                let val = convert_to_starlark_err(value_to_pyobject(*name.1))?;
-                convert_to_starlark_err(py_kwargs.set_item(key, val))?;
-            }
+    fn eval_type(&self) -> Option<Ty> {
+        let (_, ty) = self.native_type.get().members.native_type.as_ref()?;
+        Some(ty.clone())
+    }
 
-            convert_to_starlark_err(pyobject_to_value(
-                convert_to_starlark_err(self.callable.call(py, py_args_tuple, Some(&py_kwargs)))?
-                    .into_bound(py),
-                eval.heap(),
-            ))
-        })
+    fn typechecker_ty(&self) -> Option<Ty> {
+        Some(self.native_type.get().class_ty.clone())
     }
 }
 
-// }}}
+// Returns the native type of obj, if obj is a class decorated with
+// native_type (is_class) or an object of such a class.
+fn native_type_of<'py>(
+    obj: &Bound<'py, PyAny>,
+    is_class: bool,
+) -> PyResult<Option<Bound<'py, NativeType>>> {
+    let py = obj.py();
+    let cls = if is_class {
+        if !obj.is_instance_of::<PyType>() {
+            return Ok(None);
+        }
+        obj.clone()
+    } else {
+        obj.get_type().into_any()
+    };
+    // Subclasses inherit the attribute, but are not of the native type.
+    let native_type = cls
+        .getattr(intern!(py, "__dict__"))?
+        .call_method1(intern!(py, "get"), (intern!(py, "__starlark_type__"),))?;
+    Ok(native_type.downcast_into::<NativeType>().ok())
+}
 
-// {{{ Module
+/// Return a class decorator that makes objects of the class available to
+/// Starlark as objects of a native type, named *name* or else after the
+/// class. Like for :class:`OpaquePythonObject`, the attributes named in
+/// *attrs* may be read and the methods named in *methods* may be called,
+/// and comparison, hashing and :func:`repr` are those of the object.
+/// Subclasses are converted as usual unless they are decorated, too.
+///
+/// Converting the class itself to Starlark, e.g. with
+/// :meth:`GlobalsBuilder.set`, yields a value that constructs objects when
+/// called and that may be used in type annotations. Type annotations of the
+/// attributes, methods and constructor are made known to
+/// :meth:`AstModule.typecheck` as for :meth:`Module.add_callable`.
+///
+/// .. versionadded:: 2025.2.6
+#[pyfunction]
+#[pyo3(
+    signature = (*, name=None, attrs=Vec::new(), methods=Vec::new()),
+    text_signature = "(*, name: str | None = None, attrs: Sequence[str] = (), methods: Sequence[str] = ()) -> Callable[[type], type]"
+)]
+fn native_type(
+    py: Python<'_>,
+    name: Option<String>,
+    attrs: Vec<String>,
+    methods: Vec<String>,
+) -> PyResult<Bound<'_, PyCFunction>> {
+    PyCFunction::new_closure(py, None, None, move |args, _kwargs| -> PyResult<Py<PyAny>> {
+        let (cls,): (Bound<PyType>,) = args.extract()?;
+        let native_type = NativeType::new(&cls, name.clone(), &attrs, &methods)?;
+        cls.setattr(intern!(cls.py(), "__starlark_type__"), native_type)?;
+        Ok(cls.into_any().unbind())
+    })
+}
 
-/// .. automethod:: __getitem__
-/// .. automethod:: __setitem__
-/// .. automethod:: add_callable
-/// .. automethod:: freeze
-#[pyclass]
-struct Module(Mutex<starlark::environment::Module>);
+// }}}
 
-// Rust infers that Module is not Send because Module contains 'extra_value',
-// which is a Value, and this change prevents Values from ever being Send:
-// https://github.com/facebook/starlark-rust/commit/6af7319980c5f4227b5642a49c4c520e96b5c06a
-// In our case, extra_value isn't used, so here we go pretending to know better.
-// It will probably be useful to disable this on every update to starlark to
-// see if there are other reasons for Module to not be Send.
-unsafe impl Send for Module {}
-unsafe impl Sync for Module {}
+// {{{ ConverterRegistry
 
-#[pymethods]
-impl Module {
-    #[new]
-    #[pyo3(text_signature = "() -> None")]
-    fn py_new() -> PyResult<Module> {
-        Ok(Module(Mutex::new(starlark::environment::Module::new())))
-    }
+struct Converter {
+    python_type: Py<PyType>,
+    to_starlark: Py<PyAny>,
+    from_starlark: Option<(String, Py<PyAny>)>,
+}
 
-    fn __getitem__(slf: &Bound<Self>, name: &str) -> PyResult<Py<PyAny>> {
-        Python::attach(|py| match slf.borrow().0.lock().unwrap().get(name) {
-            Some(val) => Ok(value_to_pyobject(val)?),
-            None => Ok(py.None()),
-        })
-    }
+#[derive(Default)]
+struct Converters {
+    converters: Vec<Converter>,
+    // Classes to convert Starlark records to, by name of the record type.
+    record_classes: HashMap<String, Py<PyType>>,
+}
 
-    fn __setitem__(slf: &Bound<Self>, name: &str, obj: Bound<PyAny>) -> PyResult<()> {
-        let self_ref = slf.borrow();
-        let self_locked = self_ref.0.lock().unwrap();
-        self_locked.set(name, pyobject_to_value(obj, self_locked.heap())?);
-        Ok(())
-    }
+// The number of converters that may be applied in turn to the result of
+// another, e.g. to the items of a list returned by a converter.
+const MAX_CONVERTER_DEPTH: usize = 100;
 
-    #[pyo3(text_signature = "(name: str, callable: Callable) -> None")]
-    fn add_callable(slf: &Bound<Self>, name: &str, callable: Py<PyAny>) {
-        let self_ref = slf.borrow();
-        let self_locked = self_ref.0.lock().unwrap();
-        let b = self_locked.heap().alloc(PythonCallableValue { callable });
-        self_locked.set(name, b);
-    }
+#[derive(Debug, Error)]
+enum ConverterError {
+    #[error("Converter for `{0}` returned an object of the same type")]
+    SameType(String),
+    #[error("More than {MAX_CONVERTER_DEPTH} nested conversions, at `{0}`")]
+    TooDeep(String),
+}
 
-    #[pyo3(text_signature = "() -> FrozenModule")]
-    fn freeze(slf: &Bound<Self>) -> PyResult<FrozenModule> {
-        let self_ref = slf.borrow_mut();
-        let mut self_locked = self_ref.0.lock().unwrap();
-        let module = std::mem::replace(&mut *self_locked, starlark::environment::Module::new());
-        Ok(FrozenModule(convert_freeze_err(module.freeze())?))
+// Applies the first converter registered for a type of obj, if any.
+fn convert_to_starlark<'py>(
+    options: &ConversionOptions,
+    obj: &Bound<'py, PyAny>,
+) -> PyResult<Option<Bound<'py, PyAny>>> {
+    let Some(converters) = &options.converters else {
+        return Ok(None);
+    };
+    let py = obj.py();
+    let mut found = None;
+    for converter in converters.lock_py_attached(py).unwrap().converters.iter() {
+        let python_type = converter.python_type.bind(py);
+        if obj.is_instance(python_type)? {
+            found = Some((python_type.clone(), converter.to_starlark.clone_ref(py)));
+            break;
+        }
     }
+    let Some((python_type, to_starlark)) = found else {
+        return Ok(None);
+    };
+    // Converters returning each other's types would recurse endlessly.
+    if options.converter_depth >= MAX_CONVERTER_DEPTH {
+        return Err(new_err_with_error::<StarlarkConversionError>(
+            starlark::Error::new_other(ConverterError::TooDeep(python_type.name()?.to_string())),
+        ));
+    }
+    let converted = to_starlark.bind(py).call1((obj,))?;
+    // Avoid converting the result over and over.
+    if converted.is_instance(&python_type)? {
+        return Err(new_err_with_error::<StarlarkConversionError>(
+            starlark::Error::new_other(ConverterError::SameType(python_type.name()?.to_string())),
+        ));
+    }
+    Ok(Some(converted))
 }
 
-// }}}
+fn from_starlark_converter(options: &ConversionOptions, starlark_type: &str) -> Option<Py<PyAny>> {
+    let converters = options.converters.as_ref()?;
+    Python::attach(|py| {
+        converters
+            .lock_py_attached(py)
+            .unwrap()
+            .converters
+            .iter()
+            .find_map(|converter| match &converter.from_starlark {
+                Some((name, from_starlark)) if name == starlark_type => {
+                    Some(from_starlark.clone_ref(py))
+                }
+                _ => None,
+            })
+    })
+}
 
-// {{{ FrozenModule
+fn record_class(options: &ConversionOptions, record: Value) -> Option<Py<PyType>> {
+    let converters = options.converters.as_ref()?;
+    Python::attach(|py| {
+        let converters = converters.lock_py_attached(py).unwrap();
+        if converters.record_classes.is_empty() {
+            return None;
+        }
+        // The type of a record instance is named after its record type.
+        let record_type = Ty::of_value(record).to_string();
+        converters
+            .record_classes
+            .get(&record_type)
+            .map(|cls| cls.clone_ref(py))
+    })
+}
 
-/// .. automethod:: call
-#[pyclass(frozen)]
-struct FrozenModule(starlark::environment::FrozenModule);
+/// A registry of conversions between Python objects of specific types and
+/// Starlark values, used in addition to the built-in ones described in
+/// :ref:`object-conversion`. A registry is put to use by passing it to
+/// :class:`Module`, :func:`eval` or :meth:`FrozenModule.call_with`. Converters
+/// may be registered after that.
+///
+/// .. automethod:: register
+/// .. automethod:: register_record
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct ConverterRegistry(Arc<Mutex<Converters>>);
+
+#[pymethods]
+impl ConverterRegistry {
+    #[new]
+    #[pyo3(text_signature = "() -> None")]
+    fn py_new() -> ConverterRegistry {
+        ConverterRegistry(Arc::new(Mutex::new(Converters::default())))
+    }
+
+    /// Register a conversion for instances of *python_type* (including
+    /// its subclasses). Converters are consulted in the order in which they
+    /// were registered, before any built-in conversion.
+    ///
+    /// :arg to_starlark: called with an instance of *python_type*, returns
+    ///     an object of a different type, which is then converted to
+    ///     Starlark (possibly using other converters, which may be nested
+    ///     up to 100 deep).
+    /// :arg from_starlark: if given, called with every Starlark value of the
+    ///     type *starlark_type* (as returned by Starlark's ``type()``) after
+    ///     its conversion to Python, returns the object to use instead.
+    /// :arg starlark_type: required if *from_starlark* is given.
+    #[pyo3(
+        signature = (python_type, to_starlark, from_starlark=None, *, starlark_type=None),
+        text_signature = "(python_type: type, to_starlark: Callable[[Any], object], from_starlark: Callable[[Any], object] | None = None, *, starlark_type: str | None = None) -> None"
+    )]
+    fn register(
+        &self,
+        py: Python<'_>,
+        python_type: Py<PyType>,
+        to_starlark: Py<PyAny>,
+        from_starlark: Option<Py<PyAny>>,
+        starlark_type: Option<String>,
+    ) -> PyResult<()> {
+        let from_starlark = match (starlark_type, from_starlark) {
+            (Some(starlark_type), Some(from_starlark)) => Some((starlark_type, from_starlark)),
+            (None, None) => None,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "from_starlark and starlark_type must be given together",
+                ))
+            }
+        };
+        self.0.lock_py_attached(py).unwrap().converters.push(Converter {
+            python_type,
+            to_starlark,
+            from_starlark,
+        });
+        Ok(())
+    }
+
+    /// Convert instances of the Starlark record type named *record_type*
+    /// to Python by calling *cls* with the record's fields as keyword
+    /// arguments, instead of to a :class:`dict`. *cls* is typically a
+    /// :mod:`dataclass <dataclasses>`, whose instances are in turn converted
+    /// to Starlark structs.
+    ///
+    /// :arg record_type: defaults to the name of *cls*.
+    #[pyo3(
+        signature = (cls, record_type=None),
+        text_signature = "(cls: type, record_type: str | None = None) -> None"
+    )]
+    fn register_record(
+        &self,
+        cls: &Bound<'_, PyType>,
+        record_type: Option<String>,
+    ) -> PyResult<()> {
+        let record_type = match record_type {
+            Some(record_type) => record_type,
+            None => cls.name()?.to_string(),
+        };
+        self.0
+            .lock_py_attached(cls.py())
+            .unwrap()
+            .record_classes
+            .insert(record_type, cls.clone().unbind());
+        Ok(())
+    }
+}
+
+// }}}
+
+// {{{ StarlarkStruct and StarlarkRecord
+
+// The fields of a struct or record, converted to Python.
+struct Fields(Vec<(String, Py<PyAny>)>);
+
+fn convert_fields<'v>(
+    fields: impl Iterator<Item = (&'v str, Value<'v>)>,
+    heap: &'v Heap,
+    options: &ConversionOptions,
+) -> PyResult<Fields> {
+    Ok(Fields(
+        fields
+            .map(|(name, value)| Ok((name.to_owned(), value_to_pyobject(value, heap, options)?)))
+            .collect::<PyResult<Vec<(String, Py<PyAny>)>>>()?,
+    ))
+}
+
+impl Fields {
+    fn get(&self, py: Python<'_>, type_name: &str, name: &str) -> PyResult<Py<PyAny>> {
+        match self.0.iter().find(|(field, _)| field == name) {
+            Some((_, value)) => Ok(value.clone_ref(py)),
+            None => Err(PyAttributeError::new_err(format!(
+                "'{type_name}' object has no attribute '{name}'"
+            ))),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.0.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    fn bind_items<'py>(&self, py: Python<'py>) -> Vec<(String, Bound<'py, PyAny>)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.clone(), value.bind(py).clone()))
+            .collect()
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (name, value) in &self.0 {
+            dict.set_item(name, value)?;
+        }
+        Ok(dict)
+    }
+
+    fn repr(&self, py: Python<'_>, prefix: &str) -> PyResult<String> {
+        let fields = self
+            .0
+            .iter()
+            .map(|(name, value)| Ok(format!("{name}={}", value.bind(py).repr()?)))
+            .collect::<PyResult<Vec<String>>>()?;
+        Ok(format!("{prefix}({})", fields.join(", ")))
+    }
+}
+
+/// A read-only view of a Starlark struct, whose fields are available as
+/// attributes. Returned instead of a :class:`dict` if requested (see
+/// :ref:`object-conversion`). Converted back to a struct when passed to
+/// Starlark.
+///
+/// .. automethod:: to_dict
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct StarlarkStruct(Fields);
+
+#[pymethods]
+impl StarlarkStruct {
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+        self.0.get(py, "StarlarkStruct", name)
+    }
+
+    fn __dir__(&self) -> Vec<String> {
+        let mut names = self.0.names();
+        names.push("to_dict".to_owned());
+        names
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        self.0.repr(py, "struct")
+    }
+
+    fn __eq__(&self, py: Python<'_>, other: PyRef<'_, StarlarkStruct>) -> PyResult<bool> {
+        self.0.to_dict(py)?.eq(other.0.to_dict(py)?)
+    }
+
+    /// Return the fields as a :class:`dict`. Nested values are not
+    /// converted.
+    #[pyo3(text_signature = "() -> dict[str, object]")]
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.0.to_dict(py)
+    }
+}
+
+/// A read-only view of an instance of a Starlark record type, whose fields
+/// are available as attributes. Returned instead of a :class:`dict` if
+/// requested (see :ref:`object-conversion`). Converted to a struct when
+/// passed to Starlark.
+///
+/// .. attribute:: record_type
+///
+///     A :class:`str`, the name of the record type.
+/// .. automethod:: to_dict
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct StarlarkRecord {
+    #[pyo3(get)]
+    record_type: String,
+    fields: Fields,
+}
+
+#[pymethods]
+impl StarlarkRecord {
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+        self.fields.get(py, "StarlarkRecord", name)
+    }
+
+    fn __dir__(&self) -> Vec<String> {
+        let mut names = self.fields.names();
+        names.push("record_type".to_owned());
+        names.push("to_dict".to_owned());
+        names
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        self.fields
+            .repr(py, &format!("record[{}]", self.record_type))
+    }
+
+    fn __eq__(&self, py: Python<'_>, other: PyRef<'_, StarlarkRecord>) -> PyResult<bool> {
+        Ok(self.record_type == other.record_type
+            && self.fields.to_dict(py)?.eq(other.fields.to_dict(py)?)?)
+    }
+
+    /// Return the fields as a :class:`dict`. Nested values are not
+    /// converted.
+    #[pyo3(text_signature = "() -> dict[str, object]")]
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.fields.to_dict(py)
+    }
+}
+
+// }}}
+
+// {{{ StarlarkEnumValue
+
+/// A value of a Starlark enum type, as created with the ``enum`` function
+/// of :attr:`LibraryExtension.EnumType`. Converted back to the same
+/// Starlark enum value when passed to Starlark, provided that the enum type
+/// is a global of the module.
+///
+/// Members of a Python :class:`enum.Enum` subclass are likewise converted
+/// to the value of the Starlark enum type of the same name, if there is
+/// one, whose value equals that of the member.
+///
+/// .. attribute:: enum_type
+///
+///     A :class:`str`, the name of the enum type.
+/// .. attribute:: value
+///
+///     The value, usually a :class:`str`.
+/// .. attribute:: index
+///
+///     An :class:`int`, the position of the value in the enum type.
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct StarlarkEnumValue {
+    #[pyo3(get)]
+    enum_type: String,
+    #[pyo3(get)]
+    value: Py<PyAny>,
+    #[pyo3(get)]
+    index: i32,
+}
+
+#[pymethods]
+impl StarlarkEnumValue {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("{}({})", self.enum_type, self.value.bind(py).repr()?))
+    }
+
+    fn __eq__(&self, other: PyRef<'_, StarlarkEnumValue>) -> PyResult<bool> {
+        Ok(self.enum_type == other.enum_type
+            && self.index == other.index
+            && self.value.bind(other.py()).eq(&other.value)?)
+    }
+
+    fn __hash__(&self, py: Python<'_>) -> PyResult<isize> {
+        (&self.enum_type, &self.value).into_pyobject(py)?.hash()
+    }
+}
+
+#[derive(Debug, Error)]
+enum EnumConversionError {
+    #[error("No Starlark enum type `{0}` found")]
+    NoEnumType(String),
+    #[error("Value `{1}` not found in Starlark enum type `{0}`")]
+    NoEnumValue(String, String),
+}
+
+fn enum_value_to_pyobject<'v>(
+    value: Value<'v>,
+    heap: &'v Heap,
+    options: &ConversionOptions,
+) -> PyResult<Py<PyAny>> {
+    let enum_value = StarlarkEnumValue {
+        enum_type: Ty::of_value(value).to_string(),
+        value: value_to_pyobject(enum_attr(value, "value", heap)?, heap, options)?,
+        index: enum_attr(value, "index", heap)?.unpack_i32().unwrap(),
+    };
+    Python::attach(|py| Ok(Py::new(py, enum_value)?.into_any()))
+}
+
+fn enum_attr<'v>(value: Value<'v>, name: &str, heap: &'v Heap) -> PyResult<Value<'v>> {
+    Ok(convert_starlark_err(value.get_attr(name, heap))?.unwrap())
+}
+
+// Looks up the Starlark enum type named name among the globals of the
+// target module, then among those of the frozen module being called, if any.
+fn find_enum_type<'v>(
+    target: ConversionTarget<'v>,
+    options: &ConversionOptions,
+    name: &str,
+) -> Option<Value<'v>> {
+    let module = target.module()?;
+    let value = match module.get(name) {
+        Some(value) => value,
+        None => options
+            .frozen_module
+            .as_ref()?
+            .get_option(name)
+            .ok()??
+            .owned_value(module.frozen_heap()),
+    };
+    let is_enum_type = value.downcast_ref::<EnumType>().is_some()
+        || value.downcast_ref::<FrozenEnumType>().is_some();
+    is_enum_type.then_some(value)
+}
+
+// Returns the value of the enum type named type_name equal to value, or
+// None if there is no such enum type.
+fn find_enum_value<'v>(
+    target: ConversionTarget<'v>,
+    options: &ConversionOptions,
+    type_name: &str,
+    value: Value<'v>,
+) -> PyResult<Option<Value<'v>>> {
+    let Some(enum_type) = find_enum_type(target, options, type_name) else {
+        return Ok(None);
+    };
+    let heap = target.heap();
+    for i in 0..convert_starlark_err(enum_type.length())? {
+        let element = convert_starlark_err(enum_type.at(heap.alloc(i), heap))?;
+        let element_value = convert_starlark_err(element.get_attr("value", heap))?;
+        if convert_starlark_err(value.equals(element_value.unwrap()))? {
+            return Ok(Some(element));
+        }
+    }
+    Err(new_err_with_error::<StarlarkConversionError>(
+        starlark::Error::new_other(EnumConversionError::NoEnumValue(
+            type_name.to_owned(),
+            value.to_repr(),
+        )),
+    ))
+}
+
+// Converts a member of an enum.Enum subclass to the matching Starlark enum
+// value, if the subclass has a Starlark counterpart.
+fn python_enum_to_value<'v>(
+    obj: &Bound<PyAny>,
+    target: ConversionTarget<'v>,
+    options: &ConversionOptions,
+) -> PyResult<Option<Value<'v>>> {
+    let py = obj.py();
+    let enum_class = py.import(intern!(py, "enum"))?.getattr(intern!(py, "Enum"))?;
+    if !obj.is_instance(&enum_class)? {
+        return Ok(None);
+    }
+    let type_name = obj.get_type().name()?.to_string();
+    if find_enum_type(target, options, &type_name).is_none() {
+        return Ok(None);
+    }
+    let value = pyobject_to_value(obj.getattr(intern!(py, "value"))?, target, options)?;
+    find_enum_value(target, options, &type_name, value)
+}
+
+// }}}
+
+// {{{ PythonCallableValue
+
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+struct PythonCallableValue {
+    #[allocative(skip)]
+    callable: Py<PyAny>,
+    #[allocative(skip)]
+    signature: Option<Arc<Signature>>,
+    // Whether a CallContext is passed as the first argument.
+    pass_context: bool,
+}
+starlark_simple_value!(PythonCallableValue);
+
+impl PythonCallableValue {
+    fn new(
+        callable: Bound<PyAny>,
+        signature: Option<Bound<PyAny>>,
+        pass_context: bool,
+    ) -> PyResult<PythonCallableValue> {
+        let signature =
+            Signature::from_python(&callable, signature.as_ref(), pass_context)?.map(Arc::new);
+        Ok(PythonCallableValue {
+            callable: callable.unbind(),
+            signature,
+            pass_context,
+        })
+    }
+
+    fn clone_ref(&self, py: Python<'_>) -> PythonCallableValue {
+        PythonCallableValue {
+            callable: self.callable.clone_ref(py),
+            signature: self.signature.clone(),
+            pass_context: self.pass_context,
+        }
+    }
+}
+
+impl Display for PythonCallableValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<python callable>")
+    }
+}
+
+#[starlark_value(type = "python_callable_value")]
+impl<'v> StarlarkValue<'v> for PythonCallableValue {
+    fn invoke(
+        &self,
+        _me: Value<'v>,
+        args: &Arguments<'v, '_>,
+        eval: &mut starlark::eval::Evaluator<'v, '_, '_>,
+    ) -> starlark::Result<Value<'v>> {
+        let positions: Vec<Value<'v>> = args.positions(eval.heap())?.collect();
+        let names = args.names_map()?;
+        if let Some(signature) = &self.signature {
+            let keys: Vec<&str> = names.keys().map(|name| name.as_str()).collect();
+            signature
+                .check_args(positions.len(), &keys)
+                .map_err(starlark::Error::new_other)?;
+        }
+
+        let options = ConversionOptions::from_evaluator(eval);
+        Python::attach(|py| -> starlark::Result<Value<'v>> {
+            let mut py_args: Vec<Py<PyAny>> = Vec::new();
+            if self.pass_context {
+                let context = CallContext {
+                    location: eval.call_stack_top_location(),
+                    call_stack: eval.call_stack(),
+                    module_name: options.module_name.clone(),
+                };
+                py_args.push(convert_to_starlark_err(Py::new(py, context))?.into_any());
+            }
+
+            // Handle positional arguments
+            for v in &positions {
+                py_args.push(convert_to_starlark_callable_err(value_to_pyobject(
+                    *v,
+                    eval.heap(),
+                    &options,
+                ))?);
+            }
+            let py_args_tuple = convert_to_starlark_err(PyTuple::new(py, py_args))?;
+
+            // Handle named arguments
+            let py_kwargs = PyDict::new(py);
+            for name in names.iter() {
+                let key = name.0.as_str();
+                let val = convert_to_starlark_callable_err(value_to_pyobject(
+                    *name.1,
+                    eval.heap(),
+                    &options,
+                ))?;
+                convert_to_starlark_err(py_kwargs.set_item(key, val))?;
+            }
+
+            let result = self
+                .callable
+                .call(py, py_args_tuple, Some(&py_kwargs))
+                .map_err(|e| starlark::Error::new_other(PythonCallableError(e)))?;
+            convert_to_starlark_callable_err(pyobject_to_value(
+                result.into_bound(py),
+                ConversionTarget::Module(eval.module()),
+                &options,
+            ))
+        })
+    }
+
+    fn typechecker_ty(&self) -> Option<Ty> {
+        self.signature
+            .as_ref()
+            .map(|signature| signature.ty().clone())
+    }
+}
+
+// }}}
+
+// {{{ Module
+
+/// .. automethod:: __getitem__
+/// .. automethod:: __setitem__
+/// .. automethod:: add_callable
+/// .. automethod:: freeze
+/// .. automethod:: heap_size
+/// .. automethod:: heap_summary
+/// .. attribute:: preserve_tuples
+///
+///     Whether tuples are preserved in the conversion of values between
+///     Starlark and Python for this module (see :ref:`object-conversion`),
+///     or *None* to use the global default set by
+///     :func:`set_preserve_tuples`. Carried over to the
+///     :class:`FrozenModule` by :meth:`freeze`.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: preserve_structs
+///
+///     Whether Starlark structs and records are converted to
+///     :class:`StarlarkStruct` and :class:`StarlarkRecord` for this module
+///     (see :ref:`object-conversion`), or *None* to use the global default
+///     set by :func:`set_preserve_structs`. Carried over to the
+///     :class:`FrozenModule` by :meth:`freeze`.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: converters
+///
+///     The :class:`ConverterRegistry` used in the conversion of values
+///     between Starlark and Python for this module, or *None*. Carried over
+///     to the :class:`FrozenModule` by :meth:`freeze`.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: name
+///
+///     The name of the module, as passed to Python callables in a
+///     :class:`CallContext`, or *None* to use the file name of the
+///     evaluated code in :func:`eval`. Carried over to the
+///     :class:`FrozenModule` by :meth:`freeze`.
+///
+///     .. versionadded:: 2025.2.6
+#[pyclass]
+struct Module {
+    module: Mutex<starlark::environment::Module>,
+    // The thread holding the lock on module, if any. Python code run by
+    // that thread while it holds the lock (e.g. a callable or a converter)
+    // must not wait for the lock again.
+    holder: Mutex<Option<ThreadId>>,
+    #[pyo3(get, set)]
+    name: Option<String>,
+    #[pyo3(get, set)]
+    preserve_tuples: Option<bool>,
+    #[pyo3(get, set)]
+    preserve_structs: Option<bool>,
+    #[pyo3(get, set)]
+    converters: Option<Py<ConverterRegistry>>,
+}
+
+// Rust infers that Module is not Send because Module contains 'extra_value',
+// which is a Value, and this change prevents Values from ever being Send:
+// https://github.com/facebook/starlark-rust/commit/6af7319980c5f4227b5642a49c4c520e96b5c06a
+// In our case, extra_value isn't used, so here we go pretending to know better.
+// It will probably be useful to disable this on every update to starlark to
+// see if there are other reasons for Module to not be Send.
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+// The locked starlark module of a Module.
+struct LockedModule<'a> {
+    module: MutexGuard<'a, starlark::environment::Module>,
+    holder: &'a Mutex<Option<ThreadId>>,
+}
+
+impl Deref for LockedModule<'_> {
+    type Target = starlark::environment::Module;
+
+    fn deref(&self) -> &Self::Target {
+        &self.module
+    }
+}
+
+impl DerefMut for LockedModule<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.module
+    }
+}
+
+impl Drop for LockedModule<'_> {
+    fn drop(&mut self) {
+        *self.holder.lock().unwrap() = None;
+    }
+}
+
+impl Module {
+    // Lock the module, waiting for other threads to release it. Access by
+    // the thread already holding the lock fails instead of deadlocking.
+    fn lock(&self, py: Python<'_>) -> PyResult<LockedModule<'_>> {
+        self.check_not_held()?;
+        Ok(self.locked(self.module.lock_py_attached(py).unwrap()))
+    }
+
+    // Like lock, for use while detached from the interpreter.
+    fn lock_detached(&self) -> PyResult<LockedModule<'_>> {
+        self.check_not_held()?;
+        Ok(self.locked(self.module.lock().unwrap()))
+    }
+
+    fn check_not_held(&self) -> PyResult<()> {
+        if *self.holder.lock().unwrap() == Some(thread::current().id()) {
+            return Err(PyRuntimeError::new_err(
+                "module is in use by the running evaluation or conversion",
+            ));
+        }
+        Ok(())
+    }
+
+    fn locked<'a>(
+        &'a self,
+        module: MutexGuard<'a, starlark::environment::Module>,
+    ) -> LockedModule<'a> {
+        *self.holder.lock().unwrap() = Some(thread::current().id());
+        LockedModule {
+            module,
+            holder: &self.holder,
+        }
+    }
+
+    // The conversion options of the module itself, as used for item access.
+    fn conversion_options(&self) -> ConversionOptions {
+        ConversionOptions::new(
+            self.preserve_tuples,
+            self.preserve_structs,
+            self.converters.as_ref().map(|c| c.get()),
+        )
+    }
+}
+
+#[pymethods]
+impl Module {
+    /// .. versionchanged:: 2025.2.6
+    ///
+    ///     Added *preserve_tuples*, *preserve_structs*, *converters* and
+    ///     *name*.
+    #[new]
+    #[pyo3(
+        signature = (*, preserve_tuples=None, preserve_structs=None, converters=None, name=None),
+        text_signature = "(*, preserve_tuples: bool | None = None, preserve_structs: bool | None = None, converters: ConverterRegistry | None = None, name: str | None = None) -> None"
+    )]
+    fn py_new(
+        preserve_tuples: Option<bool>,
+        preserve_structs: Option<bool>,
+        converters: Option<Py<ConverterRegistry>>,
+        name: Option<String>,
+    ) -> PyResult<Module> {
+        Ok(Module {
+            module: Mutex::new(starlark::environment::Module::new()),
+            holder: Mutex::new(None),
+            name,
+            preserve_tuples,
+            preserve_structs,
+            converters,
+        })
+    }
+
+    fn __getitem__(slf: &Bound<Self>, name: &str) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        let self_ref = slf.borrow();
+        let options = self_ref.conversion_options();
+        let self_locked = self_ref.lock(py)?;
+        match self_locked.get(name) {
+            Some(val) => Ok(value_to_pyobject(val, self_locked.heap(), &options)?),
+            None => Ok(py.None()),
+        }
+    }
+
+    fn __setitem__(slf: &Bound<Self>, name: &str, obj: Bound<PyAny>) -> PyResult<()> {
+        let self_ref = slf.borrow();
+        let options = self_ref.conversion_options();
+        let self_locked = self_ref.lock(slf.py())?;
+        self_locked.set(
+            name,
+            pyobject_to_value(obj, ConversionTarget::Module(&self_locked), &options)?,
+        );
+        Ok(())
+    }
+
+    /// Make the Python *callable* available to Starlark code as *name*.
+    ///
+    /// Exceptions raised by *callable*, or by the converters of a
+    /// :class:`ConverterRegistry` applied to its arguments and result,
+    /// propagate from :func:`eval` or :meth:`FrozenModule.call` unchanged.
+    /// Their ``__cause__`` (if not
+    /// already set) is a :exc:`StarlarkEvalError` holding the Starlark call
+    /// stack, which is also added as a note on Python 3.11 and newer.
+    ///
+    /// The signature of *callable* is obtained from :func:`inspect.signature`,
+    /// or given explicitly as an :class:`inspect.Signature` in *signature*.
+    /// Calls with too many positional arguments, unknown keyword arguments
+    /// or missing required arguments fail before *callable* is called.
+    /// Type annotations of ``None``, :class:`bool`, :class:`int`,
+    /// :class:`float`, :class:`str`, :class:`list`, :class:`dict` and unions
+    /// thereof are made known to :meth:`AstModule.typecheck`; other
+    /// annotations are treated as ``typing.Any``.
+    ///
+    /// If *pass_context* is true, *callable* receives a :class:`CallContext`
+    /// as its first argument, which is not part of its Starlark signature.
+    ///
+    /// .. versionchanged:: 2025.2.6
+    ///
+    ///     Exceptions are no longer wrapped in :exc:`StarlarkError`.
+    ///     Added *signature* and *pass_context*, and arguments are checked
+    ///     against the signature.
+    #[pyo3(
+        signature = (name, callable, *, signature=None, pass_context=false),
+        text_signature = "(name: str, callable: Callable, *, signature: inspect.Signature | None = None, pass_context: bool = False) -> None"
+    )]
+    fn add_callable(
+        slf: &Bound<Self>,
+        name: &str,
+        callable: Bound<PyAny>,
+        signature: Option<Bound<PyAny>>,
+        pass_context: bool,
+    ) -> PyResult<()> {
+        let callable = PythonCallableValue::new(callable, signature, pass_context)?;
+        let self_ref = slf.borrow();
+        let self_locked = self_ref.lock(slf.py())?;
+        let b = self_locked.heap().alloc(callable);
+        self_locked.set(name, b);
+        Ok(())
+    }
+
+    #[pyo3(text_signature = "() -> FrozenModule")]
+    fn freeze(slf: &Bound<Self>) -> PyResult<FrozenModule> {
+        let self_ref = slf.borrow();
+        let mut self_locked = self_ref.lock(slf.py())?;
+        let module = std::mem::replace(&mut *self_locked, starlark::environment::Module::new());
+        Ok(FrozenModule {
+            module: convert_freeze_err(module.freeze())?,
+            name: self_ref.name.clone(),
+            preserve_tuples: self_ref.preserve_tuples,
+            preserve_structs: self_ref.preserve_structs,
+            converters: self_ref
+                .converters
+                .as_ref()
+                .map(|c| c.clone_ref(slf.py())),
+        })
+    }
+
+    /// Return the number of bytes allocated on the module's heap.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[pyo3(text_signature = "() -> int")]
+    fn heap_size(slf: &Bound<Self>) -> PyResult<usize> {
+        let self_ref = slf.borrow();
+        let self_locked = self_ref.lock(slf.py())?;
+        Ok(self_locked.heap().allocated_bytes())
+    }
+
+    /// Return a mapping from the names of the types of values allocated on
+    /// the module's heap to a tuple ``(count, bytes)``.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[pyo3(text_signature = "() -> dict[str, tuple[int, int]]")]
+    fn heap_summary(slf: &Bound<Self>) -> PyResult<HashMap<String, (usize, usize)>> {
+        let self_ref = slf.borrow();
+        let self_locked = self_ref.lock(slf.py())?;
+        Ok(self_locked.heap().allocated_summary().summary())
+    }
+}
+
+// }}}
+
+// {{{ FrozenModule
+
+/// .. automethod:: call
+/// .. automethod:: call_with
+/// .. automethod:: proxy
+/// .. automethod:: memory_flamegraph
+/// .. attribute:: preserve_tuples
+///
+///     See :attr:`Module.preserve_tuples`. Read-only.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: preserve_structs
+///
+///     See :attr:`Module.preserve_structs`. Read-only.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: converters
+///
+///     See :attr:`Module.converters`. Read-only.
+///
+///     .. versionadded:: 2025.2.6
+/// .. attribute:: name
+///
+///     See :attr:`Module.name`. Read-only.
+///
+///     .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct FrozenModule {
+    module: starlark::environment::FrozenModule,
+    #[pyo3(get)]
+    name: Option<String>,
+    #[pyo3(get)]
+    preserve_tuples: Option<bool>,
+    #[pyo3(get)]
+    preserve_structs: Option<bool>,
+    #[pyo3(get)]
+    converters: Option<Py<ConverterRegistry>>,
+}
 
 #[pymethods]
 impl FrozenModule {
+    /// Return the global *name* as a :class:`StarlarkValue`, which is
+    /// converted to Python only as its parts are accessed.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[pyo3(text_signature = "(name: str) -> StarlarkValue")]
+    fn proxy(&self, name: &str) -> PyResult<StarlarkValueProxy> {
+        let mut options = ConversionOptions::new(
+            self.preserve_tuples,
+            self.preserve_structs,
+            self.converters.as_ref().map(|c| c.get()),
+        );
+        options.frozen_module = Some(self.module.dupe());
+        options.owner = Some(self.module.frozen_heap().dupe());
+        options.module_name = self.name.clone();
+        Ok(StarlarkValueProxy {
+            value: convert_anyhow_err(self.module.get(name))?,
+            options,
+        })
+    }
+
+    /// Return a report of the memory retained by the module, in the
+    /// line-based input format of ``flamegraph.pl``, as gathered by
+    /// `allocative <https://docs.rs/allocative>`__.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[pyo3(text_signature = "() -> str")]
+    fn memory_flamegraph(&self) -> String {
+        let mut builder = allocative::FlameGraphBuilder::default();
+        builder.visit_root(&self.module);
+        builder.finish_and_write_flame_graph()
+    }
+
+    /// Call the function *name* of the module with the positional
+    /// arguments *args* and the keyword arguments *kwargs*, converted as
+    /// described in :ref:`object-conversion`. To set limits or conversion
+    /// options for the call, use :meth:`call_with`.
+    ///
     /// .. versionadded:: 2025.2.2
     /// .. versionchanged:: 2025.2.3
     ///
@@ -1060,38 +3452,395 @@ impl FrozenModule {
         args: &Bound<'_, PyTuple>,
         kwargs: Option<&Bound<'_, PyDict>>,
     ) -> PyResult<Py<PyAny>> {
-        let function = convert_anyhow_err(slf.get().0.get(name))?;
+        let this = slf.get();
+        let options = this.call_options(None, None, None);
+        let function = convert_anyhow_err(this.module.get(name))?;
+        call_frozen_function(
+            slf.py(),
+            &function,
+            args,
+            kwargs,
+            EvalLimits::default(),
+            &options,
+        )
+    }
+
+    /// Like :meth:`call`, with the arguments of the function passed as the
+    /// sequence *args* and the dictionary *kwargs*.
+    ///
+    /// :arg max_steps: see :func:`eval`.
+    /// :arg timeout: see :func:`eval`.
+    /// :arg cancellation_token: see :func:`eval`.
+    /// :arg max_heap_bytes: see :func:`eval`.
+    /// :arg max_callstack_depth: see :func:`eval`.
+    /// :arg preserve_tuples: see :func:`eval`. Defaults to
+    ///     :attr:`preserve_tuples`.
+    /// :arg preserve_structs: see :func:`eval`. Defaults to
+    ///     :attr:`preserve_structs`.
+    /// :arg converters: see :func:`eval`. Defaults to :attr:`converters`.
+    ///
+    /// .. versionadded:: 2025.2.6
+    #[pyo3(
+        signature = (
+            name, args=None, kwargs=None, *,
+            max_steps=None, timeout=None, cancellation_token=None, max_heap_bytes=None,
+            max_callstack_depth=None, preserve_tuples=None, preserve_structs=None,
+            converters=None
+        ),
+        text_signature = "(name: str, args: Sequence[object] = (), kwargs: Mapping[str, object] | None = None, *, max_steps: int | None = None, timeout: float | None = None, cancellation_token: CancellationToken | None = None, max_heap_bytes: int | None = None, max_callstack_depth: int | None = None, preserve_tuples: bool | None = None, preserve_structs: bool | None = None, converters: ConverterRegistry | None = None) -> object"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn call_with(
+        slf: &Bound<'_, FrozenModule>,
+        name: &str,
+        args: Option<Vec<Bound<'_, PyAny>>>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+        max_steps: Option<u64>,
+        timeout: Option<f64>,
+        cancellation_token: Option<&Bound<'_, CancellationToken>>,
+        max_heap_bytes: Option<usize>,
+        max_callstack_depth: Option<usize>,
+        preserve_tuples: Option<bool>,
+        preserve_structs: Option<bool>,
+        converters: Option<&Bound<'_, ConverterRegistry>>,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        let limits = EvalLimits::new(
+            max_steps,
+            timeout,
+            cancellation_token.map(|t| t.get()),
+            max_heap_bytes,
+            max_callstack_depth,
+        )?;
+        let this = slf.get();
+        let options = this.call_options(
+            preserve_tuples,
+            preserve_structs,
+            converters.map(|c| c.get()),
+        );
+        let args = PyTuple::new(py, args.unwrap_or_default())?;
+        let function = convert_anyhow_err(this.module.get(name))?;
+        call_frozen_function(py, &function, &args, kwargs, limits, &options)
+    }
+}
+
+impl FrozenModule {
+    // The conversion options for calls, defaulting to those of the module.
+    fn call_options(
+        &self,
+        preserve_tuples: Option<bool>,
+        preserve_structs: Option<bool>,
+        converters: Option<&ConverterRegistry>,
+    ) -> ConversionOptions {
+        let mut options = ConversionOptions::new(
+            preserve_tuples.or(self.preserve_tuples),
+            preserve_structs.or(self.preserve_structs),
+            converters.or(self.converters.as_ref().map(|c| c.get())),
+        );
+        options.frozen_module = Some(self.module.dupe());
+        options.module_name = self.name.clone();
+        options
+    }
+}
+
+// }}}
+
+// {{{ StarlarkValue
+
+// Types whose values are converted rather than wrapped in a StarlarkValue.
+const CONVERTED_TYPES: [&str; 6] = ["NoneType", "bool", "int", "float", "string", "function"];
+
+/// A read-only view of a value in a :class:`FrozenModule`, as returned by
+/// :meth:`FrozenModule.proxy`. Indexing, iteration, :func:`len`, ``in``
+/// and attribute access are supported, and yield further
+/// :class:`StarlarkValue` objects, except for ``None``, booleans, numbers,
+/// strings and functions, which are converted (see
+/// :ref:`object-conversion`). This
+/// avoids converting large values as a whole if only parts of them are
+/// needed.
+///
+/// .. automethod:: to_python
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen, name = "StarlarkValue")]
+struct StarlarkValueProxy {
+    value: OwnedFrozenValue,
+    options: ConversionOptions,
+}
+
+impl StarlarkValueProxy {
+    // Converts or wraps value, which is part of self.value.
+    fn wrap<'v>(&self, py: Python<'_>, value: Value<'v>, heap: &'v Heap) -> PyResult<Py<PyAny>> {
+        match value.unpack_frozen() {
+            Some(frozen) if !CONVERTED_TYPES.contains(&value.get_type()) => {
+                let proxy = StarlarkValueProxy {
+                    // The heap of self.value keeps its parts alive.
+                    value: self.value.map(|_| frozen),
+                    options: self.options.clone(),
+                };
+                Ok(Py::new(py, proxy)?.into_any())
+            }
+            _ => value_to_pyobject(value, heap, &self.options),
+        }
+    }
+}
+
+#[pymethods]
+impl StarlarkValueProxy {
+    fn __getitem__(&self, py: Python<'_>, key: Bound<PyAny>) -> PyResult<Py<PyAny>> {
+        let module = starlark::environment::Module::new();
+        let value = self.value.owned_value(module.frozen_heap());
+        let key = pyobject_to_value(
+            key,
+            ConversionTarget::Module(&module),
+            &self.options.for_keys(),
+        )?;
+        if let Some(dict) = DictRef::from_value(value) {
+            return match convert_starlark_err(dict.get(key))? {
+                Some(item) => self.wrap(py, item, module.heap()),
+                None => Err(PyKeyError::new_err(key.to_repr())),
+            };
+        }
+        self.wrap(
+            py,
+            convert_starlark_err(value.at(key, module.heap()))?,
+            module.heap(),
+        )
+    }
+
+    fn __contains__(&self, item: Bound<PyAny>) -> PyResult<bool> {
         let module = starlark::environment::Module::new();
-        let sl_args = args
+        let value = self.value.owned_value(module.frozen_heap());
+        let item = pyobject_to_value(
+            item,
+            ConversionTarget::Module(&module),
+            &self.options.for_keys(),
+        )?;
+        convert_starlark_err(value.is_in(item))
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(convert_starlark_err(self.value.value().length())? as usize)
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let module = starlark::environment::Module::new();
+        let value = self.value.owned_value(module.frozen_heap());
+        let items = convert_starlark_err(value.iterate(module.heap()))?
+            .map(|item| self.wrap(py, item, module.heap()))
+            .collect::<PyResult<Vec<Py<PyAny>>>>()?;
+        PyList::new(py, items)?.try_iter()
+    }
+
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+        let module = starlark::environment::Module::new();
+        let value = self.value.owned_value(module.frozen_heap());
+        match convert_starlark_err(value.get_attr(name, module.heap()))? {
+            Some(attr) => self.wrap(py, attr, module.heap()),
+            None => Err(PyAttributeError::new_err(format!(
+                "'{}' value has no attribute '{name}'",
+                value.get_type()
+            ))),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        self.value.value().to_repr()
+    }
+
+    /// Return the value converted to Python as a whole, like
+    /// :meth:`Module.__getitem__` would.
+    #[pyo3(text_signature = "() -> object")]
+    fn to_python(&self) -> PyResult<Py<PyAny>> {
+        let module = starlark::environment::Module::new();
+        let value = self.value.owned_value(module.frozen_heap());
+        value_to_pyobject(value, module.heap(), &self.options)
+    }
+}
+
+// }}}
+
+// {{{ StarlarkCallable
+
+/// A Starlark function (or other callable value) returned to Python from
+/// :meth:`FrozenModule.call` or a :class:`StarlarkValue`. Calling it calls
+/// the Starlark function, with arguments and result converted like in
+/// :meth:`FrozenModule.call`. It remains usable after the module it came
+/// from is gone. Converted back to the same function when passed to
+/// Starlark.
+///
+/// Functions in a :class:`Module` that is not frozen cannot be converted.
+///
+/// .. versionadded:: 2025.2.6
+#[pyclass(frozen)]
+struct StarlarkCallable {
+    function: OwnedFrozenValue,
+    options: ConversionOptions,
+}
+
+#[pymethods]
+impl StarlarkCallable {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        args: &Bound<'_, PyTuple>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Py<PyAny>> {
+        let limits = EvalLimits::default();
+        call_frozen_function(py, &self.function, args, kwargs, limits, &self.options)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<StarlarkCallable {}>", self.function.value().to_repr())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Function `{0}` can only be converted to Python from a frozen module")]
+struct UnfrozenFunctionError(String);
+
+#[derive(Debug, Error)]
+#[error("Starlark functions cannot be converted back to Starlark here")]
+struct CallableTargetError;
+
+fn function_to_pyobject(value: Value, options: &ConversionOptions) -> PyResult<Py<PyAny>> {
+    let owner = match (&options.owner, &options.call_heaps) {
+        (Some(owner), _) => Some(owner.dupe()),
+        (None, Some(heaps)) => {
+            let heap = FrozenHeap::new();
+            for owner in heaps.lock().unwrap().iter() {
+                heap.add_reference(owner);
+            }
+            Some(heap.into_ref())
+        }
+        (None, None) => None,
+    };
+    let (Some(owner), Some(frozen)) = (owner, value.unpack_frozen()) else {
+        return Err(new_err_with_error::<StarlarkConversionError>(
+            starlark::Error::new_other(UnfrozenFunctionError(value.to_repr())),
+        ));
+    };
+    // SAFETY: The owner of the value being converted keeps its parts alive.
+    let function = unsafe { OwnedFrozenValue::new(owner.dupe(), frozen) };
+    let mut options = options.clone();
+    options.owner = Some(owner);
+    options.call_heaps = None;
+    let callable = StarlarkCallable { function, options };
+    Python::attach(|py| Ok(Py::new(py, callable)?.into_any()))
+}
+
+// Whether value is or contains a function, as converted by
+// builtin_value_to_pyobject.
+fn contains_function(value: Value) -> bool {
+    if value.get_type() == "function" {
+        return true;
+    }
+    if let Some(dict) = DictRef::from_value(value) {
+        return dict
             .iter()
-            .map(|item| pyobject_to_value(item, module.heap()))
-            .collect::<PyResult<Vec<Value<'_>>>>()?;
-        let sl_kwargs = match kwargs {
-            Some(kwarg_seq) => kwarg_seq
-                .iter()
-                .map(|(k, v)| Ok((k.extract::<String>()?, pyobject_to_value(v, module.heap())?)))
-                .collect::<PyResult<Vec<(String, Value<'_>)>>>()?,
-            None => Vec::new(),
-        };
-        let mut evaluator = starlark::eval::Evaluator::new(&module);
-        value_to_pyobject(convert_starlark_err(
-            evaluator.eval_function(
-                function.value(),
-                &sl_args,
-                &sl_kwargs
-                    .iter()
-                    .map(|(k, v)| (k.as_str(), v.dupe()))
-                    .collect::<Vec<(&str, Value<'_>)>>(),
-            ),
-        )?)
+            .any(|(k, v)| contains_function(k) || contains_function(v));
+    }
+    if let Some(list) = ListRef::from_value(value) {
+        return list.iter().any(contains_function);
     }
+    if let Some(tuple) = TupleRef::from_value(value) {
+        return tuple.iter().any(contains_function);
+    }
+    if let Some(struct_ref) = StructRef::from_value(value) {
+        return struct_ref.iter().any(|(_, v)| contains_function(v));
+    }
+    if let Some(record) = Record::from_value(value) {
+        return record.iter().any(|(_, v)| contains_function(v));
+    }
+    false
+}
+
+// Calls function with arguments converted from Python, as for
+// FrozenModule.call.
+fn call_frozen_function(
+    py: Python<'_>,
+    function: &OwnedFrozenValue,
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+    limits: EvalLimits,
+    options: &ConversionOptions,
+) -> PyResult<Py<PyAny>> {
+    let py_args: Vec<Py<PyAny>> = args.iter().map(Bound::unbind).collect();
+    let py_kwargs = match kwargs {
+        Some(kwarg_seq) => kwarg_seq
+            .iter()
+            .map(|(k, v)| Ok((k.extract::<String>()?, v.unbind())))
+            .collect::<PyResult<Vec<(String, Py<PyAny>)>>>()?,
+        None => Vec::new(),
+    };
+    // The owner of the options belongs to the caller, not to the values
+    // coming across during the call, which are kept alive by call_heaps.
+    let mut options = options.clone();
+    options.owner = None;
+    let mut heaps = vec![function.owner().dupe()];
+    heaps.extend(options.frozen_module.as_ref().map(|m| m.frozen_heap().dupe()));
+    options.call_heaps = Some(Arc::new(Mutex::new(heaps)));
+    let options = &options;
+    py.detach(|| {
+        let module = starlark::environment::Module::new();
+        let target = ConversionTarget::Module(&module);
+        let (sl_args, sl_kwargs) = Python::attach(|py| -> PyResult<_> {
+            let sl_args = py_args
+                .into_iter()
+                .map(|item| pyobject_to_value(item.into_bound(py), target, options))
+                .collect::<PyResult<Vec<Value<'_>>>>()?;
+            let sl_kwargs = py_kwargs
+                .into_iter()
+                .map(|(k, v)| {
+                    let v = pyobject_to_value(v.into_bound(py), target, options)?;
+                    Ok((k, v))
+                })
+                .collect::<PyResult<Vec<(String, Value<'_>)>>>()?;
+            Ok((sl_args, sl_kwargs))
+        })?;
+        let mut evaluator = starlark::eval::Evaluator::new(&module);
+        evaluator.extra = Some(options);
+        let _options_guard = EvalOptionsGuard::new(options);
+        limits.install(&mut evaluator)?;
+        let result = convert_starlark_err(
+            evaluator
+                .eval_function(
+                    function.owned_value(module.frozen_heap()),
+                    &sl_args,
+                    &sl_kwargs
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.dupe()))
+                        .collect::<Vec<(&str, Value<'_>)>>(),
+                )
+                .and_then(|value| limits.check_heap(evaluator.heap()).map(|()| value)),
+        )?;
+        drop(evaluator);
+        if !contains_function(result) {
+            return value_to_pyobject(result, module.heap(), options);
+        }
+
+        // Functions in the result must be frozen to outlive this call.
+        module.set("result", result);
+        let frozen = convert_freeze_err(module.freeze())?;
+        let result = convert_anyhow_err(frozen.get("result"))?;
+        let mut options = options.clone();
+        options.owner = Some(result.owner().dupe());
+        options.call_heaps = None;
+        let module = starlark::environment::Module::new();
+        value_to_pyobject(
+            result.owned_value(module.frozen_heap()),
+            module.heap(),
+            &options,
+        )
+    })
 }
 
 // }}}
 
 // {{{ FileLoader
 
-#[pyclass]
+#[pyclass(frozen)]
 struct FileLoader {
     callable: Py<PyAny>,
 }
@@ -1114,7 +3863,7 @@ impl starlark::eval::FileLoader for FileLoader {
                         .extract(py),
                 )?;
                 // FIXME: Can this be done without cloning the module?
-                let fmod_clone = fmod.borrow(py).0.clone();
+                let fmod_clone = fmod.get().module.clone();
                 Ok(fmod_clone)
             },
         )
@@ -1125,38 +3874,130 @@ impl starlark::eval::FileLoader for FileLoader {
 
 // {{{ eval
 
+/// The GIL is released while the Starlark code runs, which allows other
+/// Python threads to make progress (and, e.g., to cancel the evaluation).
+/// Other threads using *module* wait for the evaluation to finish, while
+/// Python callables and converters run by the evaluation itself raise
+/// :exc:`RuntimeError` when they use *module*.
+///
+/// :arg max_steps: if given, the maximum number of Starlark statements
+///     that may be executed. Evaluation exceeding this budget is aborted
+///     with :exc:`StepLimitExceeded`. Note that ``pass`` is not a statement
+///     in this sense, so a loop with an empty body only counts as a single
+///     step.
+/// :arg timeout: if given, the number of seconds after which evaluation
+///     is aborted with :exc:`EvaluationCancelled`. Like the step limit, the
+///     timeout is checked before each statement, so a single long-running
+///     statement (e.g. a slow builtin or Python callable) is not interrupted
+///     and may run past the timeout.
+/// :arg cancellation_token: if given, a :class:`CancellationToken` that
+///     aborts the evaluation with :exc:`EvaluationCancelled` once cancelled.
+///     Cancellation takes effect before the next statement, in the same way
+///     as *timeout*.
+/// :arg max_heap_bytes: if given, evaluation is aborted with
+///     :exc:`MemoryLimitExceeded` once the module's heap grows beyond
+///     this many bytes (see :meth:`Module.heap_size`). The heap is only
+///     checked between statements and once more when the evaluation
+///     finishes, not during a statement. A single statement such as
+///     ``x = "x" * 10**9`` therefore allocates all of its memory, however
+///     far beyond the limit, before the evaluation is aborted. Memory that
+///     is garbage collected before the next check, or that is allocated
+///     outside the heap (e.g. by Python callables), escapes the limit
+///     entirely.
+/// :arg max_callstack_depth: if given, the maximum number of nested
+///     Starlark function calls, instead of starlark-rust's default of
+///     about 50. Deeper recursion is aborted with :exc:`StackOverflowError`.
+///     Since each call also occupies the native stack, very large values
+///     may crash the interpreter.
+/// :arg preserve_tuples: if given, whether tuples are preserved in the
+///     conversion of values between Starlark and Python, including for
+///     callables added with :meth:`Module.add_callable`. Defaults to
+///     :attr:`Module.preserve_tuples`. See :ref:`object-conversion`.
+/// :arg preserve_structs: if given, whether Starlark structs and records
+///     are converted to :class:`StarlarkStruct` and :class:`StarlarkRecord`
+///     rather than to dictionaries. Defaults to
+///     :attr:`Module.preserve_structs`.
+/// :arg converters: if given, a :class:`ConverterRegistry` to use in the
+///     conversion of values between Starlark and Python, including for
+///     callables added with :meth:`Module.add_callable`. Defaults to
+///     :attr:`Module.converters`.
 /// :returns: the value returned by the evaluation, after :ref:`object-conversion`.
+///
+/// .. versionchanged:: 2025.2.6
+///
+///     Added *max_steps*, *timeout*, *cancellation_token*,
+///     *max_heap_bytes*, *max_callstack_depth*, *preserve_tuples*,
+///     *preserve_structs* and *converters*.
 #[pyfunction]
 #[pyo3(
-    signature = (module, ast, globals, file_loader=None),
-    text_signature = "(module: Module, ast: AstModule, globals: Globals, file_loader: FileLoader | None = None) -> object"
+    signature = (
+        module, ast, globals, file_loader=None, *,
+        max_steps=None, timeout=None, cancellation_token=None, max_heap_bytes=None,
+        max_callstack_depth=None, preserve_tuples=None, preserve_structs=None,
+        converters=None
+    ),
+    text_signature = "(module: Module, ast: AstModule, globals: Globals, file_loader: FileLoader | None = None, *, max_steps: int | None = None, timeout: float | None = None, cancellation_token: CancellationToken | None = None, max_heap_bytes: int | None = None, max_callstack_depth: int | None = None, preserve_tuples: bool | None = None, preserve_structs: bool | None = None, converters: ConverterRegistry | None = None) -> object"
 )]
+#[allow(clippy::too_many_arguments)]
 fn eval(
-    module: &mut Module,
+    py: Python<'_>,
+    module: &Module,
     ast: &Bound<AstModule>,
     globals: &Globals,
     file_loader: Option<&Bound<FileLoader>>,
+    max_steps: Option<u64>,
+    timeout: Option<f64>,
+    cancellation_token: Option<&Bound<CancellationToken>>,
+    max_heap_bytes: Option<usize>,
+    max_callstack_depth: Option<usize>,
+    preserve_tuples: Option<bool>,
+    preserve_structs: Option<bool>,
+    converters: Option<&Bound<ConverterRegistry>>,
 ) -> PyResult<Py<PyAny>> {
-    let tail = |evaluator: &mut starlark::eval::Evaluator| {
-        // Stupid: eval_module consumes the AST. Clone it.
-        value_to_pyobject(convert_starlark_err(
-            evaluator.eval_module(ast.borrow().0.clone(), &globals.0),
-        )?)
-    };
+    let limits = EvalLimits::new(
+        max_steps,
+        timeout,
+        cancellation_token.map(|t| t.get()),
+        max_heap_bytes,
+        max_callstack_depth,
+    )?;
+    // Stupid: eval_module consumes the AST. Clone it.
+    let ast = ast.borrow().0.clone();
+    let loader = file_loader.map(|loader| loader.get());
+    let mut options = ConversionOptions::new(
+        preserve_tuples.or(module.preserve_tuples),
+        preserve_structs.or(module.preserve_structs),
+        converters
+            .map(|c| c.get())
+            .or(module.converters.as_ref().map(|c| c.get())),
+    );
+    options.module_name = module.name.clone().or_else(|| {
+        Some(
+            ast.file_span(starlark::codemap::Span::default())
+                .filename()
+                .to_owned(),
+        )
+    });
 
-    let mod_locked = module.0.lock_py_attached(ast.py()).unwrap();
-    match file_loader {
-        Some(loader_cell) => {
-            let loader_ref = loader_cell.borrow();
-            let mut evaluator = starlark::eval::Evaluator::new(&mod_locked);
-            evaluator.set_loader(&*loader_ref);
-            tail(&mut evaluator)
-        }
-        None => {
-            let mut evaluator = starlark::eval::Evaluator::new(&mod_locked);
-            tail(&mut evaluator)
+    py.detach(|| {
+        let mod_locked = module.lock_detached()?;
+        let mut evaluator = starlark::eval::Evaluator::new(&mod_locked);
+        if let Some(loader) = loader {
+            evaluator.set_loader(loader);
         }
-    }
+        evaluator.extra = Some(&options);
+        let _options_guard = EvalOptionsGuard::new(&options);
+        limits.install(&mut evaluator)?;
+        value_to_pyobject(
+            convert_starlark_err(
+                evaluator
+                    .eval_module(ast, &globals.0)
+                    .and_then(|value| limits.check_heap(evaluator.heap()).map(|()| value)),
+            )?,
+            mod_locked.heap(),
+            &options,
+        )
+    })
 }
 
 // }}}
@@ -1167,6 +4008,8 @@ fn starlark_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ResolvedPos>()?;
     m.add_class::<ResolvedSpan>()?;
     m.add_class::<ResolvedFileSpan>()?;
+    m.add_class::<CallFrame>()?;
+    m.add_class::<CallContext>()?;
     m.add_class::<EvalSeverity>()?;
     m.add_class::<Lint>()?;
     m.add_class::<Error>()?;
@@ -1177,13 +4020,35 @@ fn starlark_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<AstModule>()?;
     m.add_class::<LibraryExtension>()?;
     m.add_class::<Globals>()?;
+    m.add_class::<PyGlobalsBuilder>()?;
     m.add_class::<OpaquePythonObject>()?;
+    m.add_class::<NativeType>()?;
+    m.add_class::<ConverterRegistry>()?;
+    m.add_class::<StarlarkStruct>()?;
+    m.add_class::<StarlarkRecord>()?;
+    m.add_class::<StarlarkEnumValue>()?;
+    m.add_class::<StarlarkValueProxy>()?;
+    m.add_class::<StarlarkCallable>()?;
     m.add_class::<Module>()?;
     m.add_class::<FrozenModule>()?;
     m.add_class::<FileLoader>()?;
+    m.add_class::<CancellationToken>()?;
     m.add_wrapped(wrap_pyfunction!(parse))?;
     m.add_wrapped(wrap_pyfunction!(eval))?;
+    m.add_wrapped(wrap_pyfunction!(set_starlark_tracebacks))?;
+    m.add_wrapped(wrap_pyfunction!(set_preserve_tuples))?;
+    m.add_wrapped(wrap_pyfunction!(set_preserve_structs))?;
+    m.add_wrapped(wrap_pyfunction!(native_type))?;
     m.add("StarlarkError", m.py().get_type::<StarlarkError>())?;
+    m.add("StarlarkSyntaxError", m.py().get_type::<StarlarkSyntaxError>())?;
+    m.add("StarlarkEvalError", m.py().get_type::<StarlarkEvalError>())?;
+    m.add("StarlarkTypeError", m.py().get_type::<StarlarkTypeError>())?;
+    m.add("StarlarkFreezeError", m.py().get_type::<StarlarkFreezeError>())?;
+    m.add("StarlarkConversionError", m.py().get_type::<StarlarkConversionError>())?;
+    m.add("StepLimitExceeded", m.py().get_type::<StepLimitExceeded>())?;
+    m.add("EvaluationCancelled", m.py().get_type::<EvaluationCancelled>())?;
+    m.add("MemoryLimitExceeded", m.py().get_type::<MemoryLimitExceeded>())?;
+    m.add("StackOverflowError", m.py().get_type::<StackOverflowError>())?;
 
     Ok(())
 }
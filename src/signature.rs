@@ -0,0 +1,347 @@
+/*
+ * Copyright 2022 University of Illinois Board of Trustees
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Signatures of Python callables
+//!
+//! Signatures are obtained from `inspect.signature` and type annotations,
+//! so that calls from Starlark can be checked before calling into Python,
+//! and so that the typechecker knows the types of parameters and results.
+//! Types of attributes of native types are obtained likewise.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::PyModule;
+use starlark::typing::{ParamIsRequired, ParamSpec, Ty};
+use starlark::util::ArcStr;
+use thiserror::Error;
+
+static HELPER: PyOnceLock<Py<PyModule>> = PyOnceLock::new();
+
+// Describes signatures and types as plain tuples, which are turned into
+// Starlark types below. Annotations that have no Starlark counterpart
+// (including tuples, which may be converted to lists) are described as "any".
+const HELPER_SOURCE: &std::ffi::CStr = cr#"
+import inspect
+import types
+import typing
+
+_SIMPLE = {type(None): "none", bool: "bool", int: "int", float: "float", str: "str"}
+_UNIONS = (typing.Union, getattr(types, "UnionType", typing.Union))
+
+
+def describe_type(annotation):
+    if annotation is None:
+        return ("none",)
+    try:
+        return (_SIMPLE[annotation],)
+    except (KeyError, TypeError):
+        pass
+
+    origin = typing.get_origin(annotation)
+    args = typing.get_args(annotation)
+    if origin in _UNIONS:
+        return ("union", [describe_type(arg) for arg in args])
+    if annotation is list or origin is list:
+        return ("list", describe_type(args[0]) if args else ("any",))
+    if annotation is dict or origin is dict:
+        if len(args) == 2:
+            return ("dict", describe_type(args[0]), describe_type(args[1]))
+        return ("dict", ("any",), ("any",))
+    return ("any",)
+
+
+def describe_signature(func, signature):
+    hints = {}
+    if signature is None:
+        try:
+            signature = inspect.signature(func)
+        except (TypeError, ValueError):
+            return None
+        try:
+            hints = typing.get_type_hints(func)
+        except Exception:
+            pass
+
+    def describe_annotation(name, annotation):
+        annotation = hints.get(name, annotation)
+        if annotation is inspect.Parameter.empty or isinstance(annotation, str):
+            return ("any",)
+        return describe_type(annotation)
+
+    params = [
+        (
+            param.kind.name,
+            param.name,
+            param.default is inspect.Parameter.empty,
+            describe_annotation(param.name, param.annotation),
+        )
+        for param in signature.parameters.values()
+    ]
+    return params, describe_annotation("return", signature.return_annotation)
+
+
+def describe_attributes(cls, names):
+    try:
+        hints = typing.get_type_hints(cls)
+    except Exception:
+        hints = {}
+    return [
+        describe_type(hints[name]) if name in hints else ("any",)
+        for name in names
+    ]
+"#;
+
+#[derive(Debug, PartialEq)]
+enum ParamKind {
+    PositionalOnly,
+    PositionalOrKeyword,
+    VarPositional,
+    KeywordOnly,
+    VarKeyword,
+}
+
+#[derive(Debug)]
+struct Param {
+    kind: ParamKind,
+    name: String,
+    required: bool,
+    ty: Ty,
+}
+
+/// An error in the arguments of a call to a Python callable.
+#[derive(Debug, Error)]
+pub enum ArgumentError {
+    #[error("Too many positional arguments: expected at most {0}, got {1}")]
+    TooManyPositional(usize, usize),
+    #[error("Unexpected keyword argument `{0}`")]
+    UnexpectedKeyword(String),
+    #[error("Multiple values for argument `{0}`")]
+    MultipleValues(String),
+    #[error("Missing required argument `{0}`")]
+    Missing(String),
+}
+
+/// The signature of a Python callable.
+#[derive(Debug)]
+pub struct Signature {
+    params: Vec<Param>,
+    param_spec: ParamSpec,
+    ty: Ty,
+}
+
+fn helper(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    let helper = HELPER.get_or_try_init(py, || -> PyResult<Py<PyModule>> {
+        Ok(PyModule::from_code(
+            py,
+            HELPER_SOURCE,
+            c"starlark_signature.py",
+            c"_starlark_signature",
+        )?
+        .unbind())
+    })?;
+    Ok(helper.bind(py))
+}
+
+/// Return the types of the attributes `names` of `cls`, as given by its
+/// type annotations.
+pub fn attribute_types(cls: &Bound<'_, PyAny>, names: &[String]) -> PyResult<Vec<Ty>> {
+    let descriptions = helper(cls.py())?
+        .getattr("describe_attributes")?
+        .call1((cls, names))?;
+    descriptions
+        .try_iter()?
+        .map(|description| ty_from_description(&description?))
+        .collect()
+}
+
+impl Signature {
+    /// Return the signature of `func`, as given by `signature` (an
+    /// `inspect.Signature`) or else obtained from `inspect.signature`,
+    /// or `None` if `func` has no signature. If `skip_first` is set, the
+    /// first positional parameter is left out, as it is not passed from
+    /// Starlark (e.g. the call context, or `self` of a method).
+    pub fn from_python(
+        func: &Bound<'_, PyAny>,
+        signature: Option<&Bound<'_, PyAny>>,
+        skip_first: bool,
+    ) -> PyResult<Option<Signature>> {
+        let description = helper(func.py())?
+            .getattr("describe_signature")?
+            .call1((func, signature))?;
+        if description.is_none() {
+            return Ok(None);
+        }
+
+        let (param_descriptions, return_description): (Vec<Bound<PyAny>>, Bound<PyAny>) =
+            description.extract()?;
+        let mut params = param_descriptions
+            .iter()
+            .map(|param| {
+                let (kind, name, required, ty): (String, String, bool, Bound<PyAny>) =
+                    param.extract()?;
+                let kind = match kind.as_str() {
+                    "POSITIONAL_ONLY" => ParamKind::PositionalOnly,
+                    "POSITIONAL_OR_KEYWORD" => ParamKind::PositionalOrKeyword,
+                    "VAR_POSITIONAL" => ParamKind::VarPositional,
+                    "KEYWORD_ONLY" => ParamKind::KeywordOnly,
+                    _ => ParamKind::VarKeyword,
+                };
+                Ok(Param {
+                    kind,
+                    name,
+                    required,
+                    ty: ty_from_description(&ty)?,
+                })
+            })
+            .collect::<PyResult<Vec<Param>>>()?;
+        if skip_first
+            && params.first().is_some_and(|p| {
+                matches!(
+                    p.kind,
+                    ParamKind::PositionalOnly | ParamKind::PositionalOrKeyword
+                )
+            })
+        {
+            params.remove(0);
+        }
+        let param_spec = param_spec(&params)?;
+        let ty = Ty::function(
+            param_spec.clone(),
+            ty_from_description(&return_description)?,
+        );
+        Ok(Some(Signature {
+            params,
+            param_spec,
+            ty,
+        }))
+    }
+
+    /// The type of the callable, for the typechecker.
+    pub fn ty(&self) -> &Ty {
+        &self.ty
+    }
+
+    /// The types of the parameters, for the typechecker.
+    pub fn param_spec(&self) -> &ParamSpec {
+        &self.param_spec
+    }
+
+    /// Check that `positional` positional arguments and keyword arguments
+    /// with the given names are accepted.
+    pub fn check_args(&self, positional: usize, names: &[&str]) -> Result<(), ArgumentError> {
+        let has_kind = |kind| self.params.iter().any(|p| p.kind == kind);
+        let max_positional = self
+            .params
+            .iter()
+            .filter(|p| {
+                matches!(
+                    p.kind,
+                    ParamKind::PositionalOnly | ParamKind::PositionalOrKeyword
+                )
+            })
+            .count();
+        if positional > max_positional && !has_kind(ParamKind::VarPositional) {
+            return Err(ArgumentError::TooManyPositional(max_positional, positional));
+        }
+
+        let mut position = 0;
+        for param in &self.params {
+            let by_name = names.contains(&param.name.as_str());
+            let by_position = match param.kind {
+                ParamKind::PositionalOnly | ParamKind::PositionalOrKeyword => {
+                    position += 1;
+                    position <= positional
+                }
+                _ => false,
+            };
+            match param.kind {
+                ParamKind::PositionalOnly if param.required && !by_position => {
+                    return Err(ArgumentError::Missing(param.name.clone()));
+                }
+                ParamKind::PositionalOrKeyword if by_position && by_name => {
+                    return Err(ArgumentError::MultipleValues(param.name.clone()));
+                }
+                ParamKind::PositionalOrKeyword | ParamKind::KeywordOnly
+                    if param.required && !by_position && !by_name =>
+                {
+                    return Err(ArgumentError::Missing(param.name.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        if !has_kind(ParamKind::VarKeyword) {
+            for name in names {
+                let accepted = self.params.iter().any(|p| {
+                    p.name == *name
+                        && matches!(
+                            p.kind,
+                            ParamKind::PositionalOrKeyword | ParamKind::KeywordOnly
+                        )
+                });
+                if !accepted {
+                    return Err(ArgumentError::UnexpectedKeyword((*name).to_owned()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn param_spec(params: &[Param]) -> PyResult<ParamSpec> {
+    let required = |param: &Param| {
+        if param.required {
+            ParamIsRequired::Yes
+        } else {
+            ParamIsRequired::No
+        }
+    };
+    let of_kind = |kind: ParamKind| params.iter().filter(move |p| p.kind == kind);
+    let named = |p: &Param| (ArcStr::from(p.name.as_str()), required(p), p.ty.clone());
+    ParamSpec::new_parts(
+        of_kind(ParamKind::PositionalOnly).map(|p| (required(p), p.ty.clone())),
+        of_kind(ParamKind::PositionalOrKeyword).map(named),
+        of_kind(ParamKind::VarPositional).map(|p| p.ty.clone()).next(),
+        of_kind(ParamKind::KeywordOnly).map(named),
+        of_kind(ParamKind::VarKeyword).map(|p| p.ty.clone()).next(),
+    )
+    .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn ty_from_description(description: &Bound<'_, PyAny>) -> PyResult<Ty> {
+    let kind: String = description.get_item(0)?.extract()?;
+    Ok(match kind.as_str() {
+        "none" => Ty::none(),
+        "bool" => Ty::bool(),
+        "int" => Ty::int(),
+        "float" => Ty::float(),
+        "str" => Ty::string(),
+        "list" => Ty::list(ty_from_description(&description.get_item(1)?)?),
+        "dict" => Ty::dict(
+            ty_from_description(&description.get_item(1)?)?,
+            ty_from_description(&description.get_item(2)?)?,
+        ),
+        "union" => Ty::unions(
+            description
+                .get_item(1)?
+                .try_iter()?
+                .map(|item| ty_from_description(&item?))
+                .collect::<PyResult<Vec<Ty>>>()?,
+        ),
+        _ => Ty::any(),
+    })
+}
@@ -0,0 +1,148 @@
+/*
+ * Copyright 2022 University of Illinois Board of Trustees
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Starlark call stacks as Python tracebacks
+//!
+//! When enabled, exceptions raised for Starlark errors get synthetic
+//! traceback entries pointing into the Starlark source, so that Python
+//! tooling (e.g. pytest) shows Starlark frames like Python ones.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::PyModule;
+use starlark::codemap::FileSpan;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static HELPER: PyOnceLock<Py<PyModule>> = PyOnceLock::new();
+
+// Produces traceback entries by raising the exception from code objects
+// compiled to carry the Starlark file name, line number and function name.
+// The Starlark source is registered with linecache so that it can be shown.
+const HELPER_SOURCE: &std::ffi::CStr = cr#"
+import linecache
+import types
+
+
+def add_starlark_traceback(exc, entries):
+    tb = exc.__traceback__
+    for filename, source, lineno, name in reversed(entries):
+        linecache.cache[filename] = (
+            len(source), None, source.splitlines(True), filename)
+
+        code = compile("\n" * (lineno - 1) + "raise __exc", filename, "exec")
+        if hasattr(code, "co_qualname"):
+            code = code.replace(co_name=name, co_qualname=name)
+        else:
+            code = code.replace(co_name=name)
+
+        try:
+            exec(code, {"__exc": exc})
+        except BaseException as e:
+            fake_tb = e.__traceback__.tb_next
+
+        tb = types.TracebackType(
+            tb, fake_tb.tb_frame, fake_tb.tb_lasti, fake_tb.tb_lineno)
+
+    exc.__traceback__ = tb
+"#;
+
+/// Enable or disable adding the Starlark call stack to the ``__traceback__``
+/// of exceptions derived from :exc:`StarlarkError`. This is a global setting
+/// and disabled by default.
+///
+/// .. versionadded:: 2025.2.6
+#[pyfunction]
+#[pyo3(signature = (enabled=true), text_signature = "(enabled: bool = True) -> None")]
+pub fn set_starlark_tracebacks(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+// A traceback entry: file name, source, 1-based line number, function name.
+pub type Entry = (String, String, usize, String);
+
+fn entry(location: &FileSpan, name: &str) -> Entry {
+    (
+        location.filename().to_owned(),
+        location.file.source().to_owned(),
+        location.resolve().span.begin.line + 1,
+        name.to_owned(),
+    )
+}
+
+/// If enabled via :func:`set_starlark_tracebacks`, return the traceback
+/// entries for the call stack of `e`, outermost first.
+///
+/// Like in a Python traceback, each entry names the function containing its
+/// location, which for a call site is the caller of the frame's function.
+pub fn starlark_traceback(e: &starlark::Error) -> Option<Vec<Entry>> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut caller = "<module>";
+    for frame in &e.call_stack().frames {
+        if let Some(location) = &frame.location {
+            entries.push(entry(location, caller));
+        }
+        caller = &frame.name;
+    }
+    // Errors from native functions are located at their call site, which
+    // is already the location of the innermost frame.
+    let innermost = e
+        .call_stack()
+        .frames
+        .last()
+        .and_then(|f| f.location.as_ref());
+    if let Some(span) = e.span().filter(|&span| Some(span) != innermost) {
+        entries.push(entry(span, caller));
+    }
+    Some(entries)
+}
+
+/// Prepend `entries`, as obtained from `starlark_traceback`, to the
+/// traceback of `err`.
+pub fn add_starlark_traceback(py: Python<'_>, err: PyErr, entries: Vec<Entry>) -> PyErr {
+    let value = err.into_value(py).into_bound(py);
+    let result = HELPER
+        .get_or_try_init(py, || -> PyResult<Py<PyModule>> {
+            Ok(PyModule::from_code(
+                py,
+                HELPER_SOURCE,
+                c"starlark_traceback.py",
+                c"_starlark_traceback",
+            )?
+            .unbind())
+        })
+        .and_then(|helper| {
+            helper
+                .bind(py)
+                .getattr("add_starlark_traceback")?
+                .call1((&value, entries))
+        });
+    match result {
+        Ok(_) => PyErr::from_value(value.into_any()),
+        Err(helper_err) => {
+            // Failing to add the traceback must not hide the original error,
+            // so keep the failure only as its context.
+            let _ = value.setattr("__context__", helper_err.into_value(py));
+            PyErr::from_value(value.into_any())
+        }
+    }
+}
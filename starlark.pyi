@@ -22,32 +22,58 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 THE SOFTWARE.
 """
 
-from collections.abc import Mapping, Sequence
-from typing import Callable, final
+import inspect
+from collections.abc import Iterator, Mapping, Sequence
+from types import ModuleType
+from typing import Any, Callable, TypeVar, final
 
 from typing_extensions import override
 
 __all__: Sequence[str] = [
     "AstLoad",
     "AstModule",
+    "CallContext",
+    "CallFrame",
+    "CancellationToken",
+    "ConverterRegistry",
     "Dialect",
     "DialectTypes",
     "Error",
     "EvalSeverity",
+    "EvaluationCancelled",
     "FileLoader",
     "FrozenModule",
     "Globals",
+    "GlobalsBuilder",
     "Interface",
     "LibraryExtension",
     "Lint",
+    "MemoryLimitExceeded",
     "Module",
+    "NativeType",
     "OpaquePythonObject",
     "ResolvedFileSpan",
     "ResolvedPos",
     "ResolvedSpan",
+    "StackOverflowError",
+    "StarlarkCallable",
+    "StarlarkConversionError",
+    "StarlarkEnumValue",
     "StarlarkError",
+    "StarlarkEvalError",
+    "StarlarkFreezeError",
+    "StarlarkRecord",
+    "StarlarkStruct",
+    "StarlarkSyntaxError",
+    "StarlarkTypeError",
+    "StarlarkValue",
+    "StepLimitExceeded",
     "eval",
+    "native_type",
     "parse",
+    "set_preserve_structs",
+    "set_preserve_tuples",
+    "set_starlark_tracebacks",
 ]
 
 @final
@@ -65,7 +91,39 @@ class ResolvedFileSpan:
     file: str
     span: ResolvedSpan
 
-class StarlarkError(Exception): ...
+@final
+class CallFrame:
+    @property
+    def name(self) -> str: ...
+    @property
+    def location(self) -> ResolvedFileSpan | None: ...
+
+@final
+class CallContext:
+    @property
+    def location(self) -> ResolvedFileSpan | None: ...
+    @property
+    def call_stack(self) -> list[CallFrame]: ...
+    @property
+    def module_name(self) -> str | None: ...
+
+class StarlarkError(Exception):
+    error: Error
+    call_stack: list[CallFrame]
+
+class StarlarkSyntaxError(StarlarkError): ...
+class StarlarkEvalError(StarlarkError): ...
+class StarlarkTypeError(StarlarkEvalError): ...
+class StarlarkFreezeError(StarlarkError): ...
+class StarlarkConversionError(StarlarkError): ...
+class StepLimitExceeded(StarlarkEvalError): ...
+class EvaluationCancelled(StarlarkEvalError): ...
+class MemoryLimitExceeded(StarlarkEvalError): ...
+class StackOverflowError(StarlarkEvalError): ...
+
+def set_starlark_tracebacks(enabled: bool = True) -> None: ...
+def set_preserve_tuples(enabled: bool = True) -> None: ...
+def set_preserve_structs(enabled: bool = True) -> None: ...
 
 @final
 class EvalSeverity:
@@ -129,6 +187,8 @@ class AstModule:
     def typecheck(self,
                 globals: Globals,
                 loads: dict[str, Interface],
+                *,
+                module: Module | None = None,
             ) -> tuple[list[Error], Interface, None]:
         ...
 
@@ -152,7 +212,69 @@ class LibraryExtension:
 
 @final
 class OpaquePythonObject:
-    def __new__(cls, obj: object) -> OpaquePythonObject: ...
+    def __new__(
+        cls,
+        obj: object,
+        *,
+        attrs: Sequence[str] = (),
+        methods: Sequence[str] = (),
+        fully_opaque: bool = False,
+    ) -> OpaquePythonObject: ...
+
+@final
+class NativeType:
+    @property
+    def name(self) -> str: ...
+
+_T = TypeVar("_T", bound=type)
+
+def native_type(
+    *,
+    name: str | None = None,
+    attrs: Sequence[str] = (),
+    methods: Sequence[str] = (),
+) -> Callable[[_T], _T]: ...
+
+@final
+class ConverterRegistry:
+    def register(
+        self,
+        python_type: type,
+        to_starlark: Callable[[Any], object],
+        from_starlark: Callable[[Any], object] | None = None,
+        *,
+        starlark_type: str | None = None,
+    ) -> None: ...
+    def register_record(self, cls: type, record_type: str | None = None) -> None: ...
+
+@final
+class StarlarkStruct:
+    def __getattr__(self, name: str) -> object: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    def to_dict(self) -> dict[str, object]: ...
+
+@final
+class StarlarkRecord:
+    @property
+    def record_type(self) -> str: ...
+    def __getattr__(self, name: str) -> object: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    def to_dict(self) -> dict[str, object]: ...
+
+@final
+class StarlarkEnumValue:
+    @property
+    def enum_type(self) -> str: ...
+    @property
+    def value(self) -> object: ...
+    @property
+    def index(self) -> int: ...
+    @override
+    def __eq__(self, other: object) -> bool: ...
+    @override
+    def __hash__(self) -> int: ...
 
 @final
 class Globals:
@@ -160,26 +282,128 @@ class Globals:
     def standard() -> Globals: ...
     @staticmethod
     def extended_by(extensions: list[LibraryExtension]) -> Globals: ...
+    @staticmethod
+    def builder() -> GlobalsBuilder: ...
+
+@final
+class GlobalsBuilder:
+    def __new__(cls) -> GlobalsBuilder: ...
+    @staticmethod
+    def standard() -> GlobalsBuilder: ...
+    @staticmethod
+    def extended_by(extensions: list[LibraryExtension]) -> GlobalsBuilder: ...
+    def add_callable(
+        self,
+        name: str,
+        callable: Callable[..., object],
+        *,
+        signature: inspect.Signature | None = None,
+        pass_context: bool = False,
+    ) -> None: ...
+    def set(self, name: str, value: object) -> None: ...
+    def add_namespace(
+        self,
+        name: str,
+        members: Mapping[str, object] | ModuleType,
+    ) -> None: ...
+    def without(self, names: Sequence[str]) -> GlobalsBuilder: ...
+    def only(self, names: Sequence[str]) -> GlobalsBuilder: ...
+    def build(self) -> Globals: ...
+
+@final
+class StarlarkValue:
+    def __getitem__(self, key: object, /) -> object: ...
+    def __contains__(self, item: object, /) -> bool: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[object]: ...
+    def __getattr__(self, name: str) -> object: ...
+    def to_python(self) -> object: ...
+
+@final
+class StarlarkCallable:
+    def __call__(self, *args: object, **kwargs: object) -> object: ...
 
 @final
 class FrozenModule:
+    @property
+    def preserve_tuples(self) -> bool | None: ...
+    @property
+    def preserve_structs(self) -> bool | None: ...
+    @property
+    def converters(self) -> ConverterRegistry | None: ...
+    @property
+    def name(self) -> str | None: ...
     def call(self, name: str, *args: object, **kwargs: object) -> object: ...
+    def call_with(
+        self,
+        name: str,
+        args: Sequence[object] = (),
+        kwargs: Mapping[str, object] | None = None,
+        *,
+        max_steps: int | None = None,
+        timeout: float | None = None,
+        cancellation_token: CancellationToken | None = None,
+        max_heap_bytes: int | None = None,
+        max_callstack_depth: int | None = None,
+        preserve_tuples: bool | None = None,
+        preserve_structs: bool | None = None,
+        converters: ConverterRegistry | None = None,
+    ) -> object: ...
+    def proxy(self, name: str) -> StarlarkValue: ...
+    def memory_flamegraph(self) -> str: ...
 
 @final
 class Module:
+    preserve_tuples: bool | None
+    preserve_structs: bool | None
+    converters: ConverterRegistry | None
+    name: str | None
+
+    def __new__(
+        cls,
+        *,
+        preserve_tuples: bool | None = None,
+        preserve_structs: bool | None = None,
+        converters: ConverterRegistry | None = None,
+        name: str | None = None,
+    ) -> Module: ...
     def __getitem__(self, key: str, /) -> object: ...
     def __setitem__(self, key: str, value: object, /) -> None: ...
-    def add_callable(self, name: str, callable: Callable[..., object]) -> None: ...
+    def add_callable(
+        self,
+        name: str,
+        callable: Callable[..., object],
+        *,
+        signature: inspect.Signature | None = None,
+        pass_context: bool = False,
+    ) -> None: ...
     def freeze(self) -> FrozenModule: ...
+    def heap_size(self) -> int: ...
+    def heap_summary(self) -> dict[str, tuple[int, int]]: ...
 
 @final
 class FileLoader:
     def __new__(cls, load_func: Callable[[str], FrozenModule]) -> FileLoader: ...
 
+@final
+class CancellationToken:
+    def cancel(self) -> None: ...
+    @property
+    def cancelled(self) -> bool: ...
+
 def parse(filename: str, content: str, dialect: Dialect | None = None) -> AstModule: ...
 def eval(
     module: Module,
     ast: AstModule,
     globals: Globals,
     file_loader: FileLoader | None = None,
+    *,
+    max_steps: int | None = None,
+    timeout: float | None = None,
+    cancellation_token: CancellationToken | None = None,
+    max_heap_bytes: int | None = None,
+    max_callstack_depth: int | None = None,
+    preserve_tuples: bool | None = None,
+    preserve_structs: bool | None = None,
+    converters: ConverterRegistry | None = None,
 ) -> object: ...
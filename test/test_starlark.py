@@ -1,4 +1,15 @@
+import datetime
+import enum
+import gc
+import threading
+import time
+import traceback
+import uuid
 from dataclasses import dataclass
+from pathlib import Path
+from typing import NamedTuple
+
+import pytest
 
 import starlark as sl
 
@@ -72,6 +83,276 @@ def test_python_callable_with_kwargs():
     assert val == 10
 
 
+def test_python_callable_exception():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+
+    class DomainError(Exception):
+        pass
+
+    def deny(path: str):
+        raise PermissionError(13, "denied", path)
+
+    def chained():
+        try:
+            {}["x"]
+        except KeyError as e:
+            raise DomainError("chained") from e
+
+    mod.add_callable("deny", deny)
+    mod.add_callable("chained", chained)
+    sl.eval(mod, sl.parse("callable-exc.star", "def f(p):\n    deny(p)\n"), glb)
+
+    with pytest.raises(PermissionError) as exc_info:
+        sl.eval(mod, sl.parse("main.star", "f('/etc')"), glb)
+    exc = exc_info.value
+    assert exc.args == (13, "denied")
+    assert exc.filename == "/etc"
+    assert isinstance(exc.__cause__, sl.StarlarkEvalError)
+    assert len(exc.__cause__.call_stack) == 2
+    assert exc.__cause__.call_stack[0].name == "f"
+    if hasattr(exc, "__notes__"):
+        assert "callable-exc.star" in exc.__notes__[0]
+
+    # an existing cause is preserved
+    with pytest.raises(DomainError) as exc_info:
+        sl.eval(mod, sl.parse("main.star", "chained()"), glb)
+    assert isinstance(exc_info.value.__cause__, KeyError)
+
+    fmod = mod.freeze()
+    with pytest.raises(PermissionError):
+        fmod.call("f", "/etc")
+
+    # so are exceptions raised by converters of arguments and results
+    class Secret:
+        pass
+
+    def reveal(secret):
+        raise PermissionError(13, "secret")
+
+    registry = sl.ConverterRegistry()
+    registry.register(Secret, reveal, reveal, starlark_type="struct")
+    glb = sl.Globals.extended_by([sl.LibraryExtension.StructType])
+    mod = sl.Module(converters=registry)
+    mod.add_callable("make_secret", Secret)
+    mod.add_callable("ident", lambda x: x)
+    with pytest.raises(PermissionError) as exc_info:
+        sl.eval(mod, sl.parse("secret.star", "make_secret()"), glb)
+    assert isinstance(exc_info.value.__cause__, sl.StarlarkEvalError)
+    with pytest.raises(PermissionError):
+        sl.eval(mod, sl.parse("secret.star", "ident(struct(a=1))"), glb)
+
+
+def test_python_callable_signature():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+
+    calls = []
+
+    def scale(x: int, factor: int = 2, *, offset: int = 0) -> int:
+        calls.append(x)
+        return factor * x + offset
+
+    def anything(*args, **kwargs):
+        return len(args) + len(kwargs)
+
+    mod.add_callable("scale", scale)
+    mod.add_callable("anything", anything)
+    mod.add_callable("py_len", len)
+
+    assert sl.eval(mod, sl.parse("sig.star", "scale(3, offset=1)"), glb) == 7
+    assert sl.eval(mod, sl.parse("sig.star", "anything(1, 2, a=3)"), glb) == 3
+    assert sl.eval(mod, sl.parse("sig.star", "py_len([1, 2])"), glb) == 2
+
+    for call, msg in [
+        ("scale()", "Missing required argument `x`"),
+        ("scale(1, 2, 3)", "Too many positional arguments"),
+        ("scale(1, offsett=1)", "Unexpected keyword argument `offsett`"),
+        ("scale(1, x=1)", "Multiple values for argument `x`"),
+    ]:
+        with pytest.raises(sl.StarlarkEvalError, match=msg):
+            sl.eval(mod, sl.parse("sig.star", call), glb)
+    assert calls == [3]
+
+    # an explicit signature takes precedence
+    import inspect
+
+    mod.add_callable("strict", anything, signature=inspect.signature(scale))
+    with pytest.raises(sl.StarlarkEvalError, match="Missing required argument"):
+        sl.eval(mod, sl.parse("sig.star", "strict()"), glb)
+
+
+def test_python_callable_context():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+
+    contexts = []
+
+    def audit(ctx: sl.CallContext, path: str) -> str:
+        contexts.append(ctx)
+        return path
+
+    mod.add_callable("audit", audit, pass_context=True)
+    ast = sl.parse("main.star", "def f(p):\n    return audit(p)\n\nf('x')\n")
+    assert sl.eval(mod, ast, glb) == "x"
+    with pytest.raises(sl.StarlarkEvalError, match="Too many positional"):
+        sl.eval(mod, sl.parse("main.star", "audit('x', 'y')"), glb)
+
+    ctx, = contexts
+    assert ctx.module_name == "main.star"
+    assert ctx.location.file == "main.star"
+    assert ctx.location.span.begin.line == 1
+    assert [frame.name for frame in ctx.call_stack][0] == "f"
+
+    lib = sl.Module(name="lib")
+    lib.add_callable("audit", audit, pass_context=True)
+    sl.eval(lib, sl.parse("lib.star", "def g():\n    return audit(path='y')\n"), glb)
+    assert lib.freeze().call("g") == "y"
+    assert contexts[-1].module_name == "lib"
+
+
+def test_globals_builder():
+    def read_text(path: str) -> str:
+        return f"contents of {path}"
+
+    builder = sl.GlobalsBuilder.standard()
+    builder.add_callable("double", lambda x: 2 * x)
+    builder.set("LIMITS", {"max": 3})
+    builder.add_namespace("fs", {"read_text": read_text, "SEP": "/"})
+    glb = builder.build()
+
+    def load(name: str):
+        mod = sl.Module()
+        sl.eval(mod, sl.parse(name, "quad = lambda x: double(double(x))"), glb)
+        return mod.freeze()
+
+    mod = sl.Module()
+    ast = sl.parse("main.star", """
+load("lib.star", "quad")
+[quad(LIMITS["max"]), fs.read_text("a" + fs.SEP + "b"), len([1])]
+""")
+    assert sl.eval(mod, ast, glb, sl.FileLoader(load)) == [12, "contents of a/b", 1]
+
+    # without standard globals
+    empty = sl.GlobalsBuilder()
+    empty.add_callable("double", lambda x: 2 * x)
+    with pytest.raises(sl.StarlarkEvalError, match="len"):
+        sl.eval(sl.Module(), sl.parse("main.star", "len([double(1)])"), empty.build())
+
+    dialect = sl.Dialect.extended()
+    dialect.enable_types = sl.DialectTypes.ENABLE
+    ast = sl.parse("tc.star", "def f():\n    fs.read_text(1)\n", dialect)
+    errs, _iface, _ = ast.typecheck(glb, {})
+    assert len(errs) == 1
+
+
+def test_globals_namespaces():
+    import math
+    import types
+
+    fs = types.ModuleType("fs")
+    fs.__all__ = ["read_text", "SEP"]
+    fs.read_text = lambda path: f"contents of {path}"
+    fs.SEP = "/"
+    fs.hidden = "not exported"
+
+    builder = sl.GlobalsBuilder.standard()
+    builder.add_namespace("fs", fs)
+    builder.add_namespace("math", math)
+    builder.add_namespace("net.dns", {"resolve": lambda host: "127.0.0.1"})
+    builder.add_namespace("net", {"TIMEOUT": 5})
+    glb = builder.build()
+
+    mod = sl.Module()
+    ast = sl.parse("main.star", """
+[fs.read_text("a"), math.sqrt(16), math.pi > 3, net.dns.resolve("localhost"),
+ net.TIMEOUT]
+""")
+    assert sl.eval(mod, ast, glb) == ["contents of a", 4.0, True, "127.0.0.1", 5]
+
+    with pytest.raises(sl.StarlarkEvalError):
+        sl.eval(mod, sl.parse("main.star", "fs.hidden"), glb)
+    with pytest.raises(sl.StarlarkEvalError):
+        sl.eval(mod, sl.parse("main.star", "read_text('a')"), glb)
+
+    # without __all__, only the members defined in the module itself are used
+    tools = types.ModuleType("tools")
+
+    def shout(s):
+        return s.upper()
+    shout.__module__ = "tools"
+    tools.shout = shout
+    tools.VERSION = "v1"
+    tools.helper = lambda: "imported"
+    builder = sl.GlobalsBuilder.standard()
+    builder.add_namespace("tools", tools)
+    glb = builder.build()
+    assert sl.eval(mod, sl.parse("main.star", "tools.shout(tools.VERSION)"), glb) \
+        == "V1"
+    with pytest.raises(sl.StarlarkEvalError):
+        sl.eval(mod, sl.parse("main.star", "tools.helper()"), glb)
+
+    # errors other than conversion errors are not swallowed
+    tools.CONFIG = object()
+    with pytest.raises(TypeError):
+        sl.GlobalsBuilder.standard().add_namespace("tools", tools)
+
+
+def test_globals_builder_selective():
+    builder = sl.GlobalsBuilder.extended_by(
+        [sl.LibraryExtension.Json, sl.LibraryExtension.Print])
+    builder.without(["print", "getattr"])
+    builder.add_callable("log", lambda msg: None)
+    glb = builder.build()
+
+    mod = sl.Module()
+    assert sl.eval(mod, sl.parse("main.star", "json.encode(len([1]))"), glb) == "1"
+    assert sl.eval(mod, sl.parse("main.star", "log('x')"), glb) is None
+    for src in ["print('x')", "getattr(1, 'x')"]:
+        with pytest.raises(sl.StarlarkEvalError, match="not found"):
+            sl.eval(mod, sl.parse("main.star", src), glb)
+
+    builder = sl.GlobalsBuilder.standard()
+    builder.only(["len", "str"])
+    builder.add_callable("log", lambda msg: None)
+    glb = builder.build()
+    assert sl.eval(mod, sl.parse("main.star", "log(str(len([1])))"), glb) is None
+    with pytest.raises(sl.StarlarkEvalError, match="not found"):
+        sl.eval(mod, sl.parse("main.star", "dir(1)"), glb)
+
+    with pytest.raises(ValueError, match="prnt"):
+        builder.without(["prnt"])
+
+    # calls may be chained
+    glb = sl.Globals.builder().only(["len", "str"]).without(["str"]).build()
+    assert sl.eval(mod, sl.parse("main.star", "len([1, 2])"), glb) == 2
+    with pytest.raises(sl.StarlarkEvalError, match="not found"):
+        sl.eval(mod, sl.parse("main.star", "str(1)"), glb)
+
+
+def test_python_callable_typecheck():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+
+    def greet(name: str, times: int = 1) -> str:
+        return name * times
+
+    mod.add_callable("greet", greet)
+
+    dialect = sl.Dialect.extended()
+    dialect.enable_types = sl.DialectTypes.ENABLE
+
+    # only function bodies are typechecked
+    ast = sl.parse("tc.star", "def f():\n    greet('a', 2) + 'b'\n", dialect)
+    errs, _iface, _ = ast.typecheck(glb, {}, module=mod)
+    assert not errs
+
+    for call in ["greet(1)", "greet('a', times='x')", "greet('a').append(1)"]:
+        ast = sl.parse("tc.star", f"def f():\n    {call}\n", dialect)
+        errs, _iface, _ = ast.typecheck(glb, {}, module=mod)
+        assert len(errs) == 1, call
+
+
 ADD_STAR = """
 def add(x, y, a, b):
     if a != "a":
@@ -82,6 +363,34 @@ def add(x, y, a, b):
 """
 
 
+def test_module_reentry():
+    glb = sl.Globals.extended_by([sl.LibraryExtension.StructType])
+    mod = sl.Module()
+    mod["x"] = 1
+    mod.add_callable("peek", lambda: mod["x"])
+    mod.add_callable("size", lambda: mod.heap_size())
+    mod.add_callable("freeze", lambda: mod.freeze())
+
+    # using the module from within its own evaluation fails, not deadlocks
+    with pytest.raises(RuntimeError):
+        sl.eval(mod, sl.parse("peek.star", "peek()"), glb)
+    with pytest.raises(RuntimeError):
+        sl.eval(mod, sl.parse("size.star", "size()"), glb)
+    with pytest.raises(RuntimeError, match="in use"):
+        sl.eval(mod, sl.parse("freeze.star", "freeze()"), glb)
+    assert mod["x"] == 1
+
+    registry = sl.ConverterRegistry()
+    registry.register(
+        Point, lambda p: {"x": p.x, "y": p.y},
+        lambda d: Point(mod["x"], d["y"]), starlark_type="struct")
+    mod.converters = registry
+    sl.eval(mod, sl.parse("point.star", "p = struct(x=2, y=3)"), glb)
+    with pytest.raises(RuntimeError):
+        mod["p"]
+    assert mod["x"] == 1
+
+
 def test_call_starlark():
     ast = sl.parse("add.star", ADD_STAR)
     glb = sl.Globals.standard()
@@ -89,6 +398,22 @@ def test_call_starlark():
     sl.eval(mod, ast, glb)
     fmod = mod.freeze()
     assert fmod.call("add", 3, 4, b="b", a="a") == 7
+    assert fmod.call_with("add", [3, 4], {"b": "b", "a": "a"}) == 7
+
+
+def test_call_starlark_option_named_kwargs():
+    ast = sl.parse("fetch.star", """
+def fetch(url, timeout=30, max_steps=None):
+    return [url, timeout, max_steps]
+""")
+    mod = sl.Module()
+    sl.eval(mod, ast, sl.Globals.standard())
+    fmod = mod.freeze()
+
+    # keyword arguments of call are all passed on to the function
+    assert fmod.call("fetch", "u", timeout=5, max_steps=7) == ["u", 5, 7]
+    assert fmod.call_with(
+        "fetch", ["u"], {"timeout": 5}, max_steps=1000) == ["u", 5, None]
 
 # }}}
 
@@ -181,7 +506,508 @@ def test_ext_type_conversion():
     fmod = mod.freeze()
     retval = fmod.call("get_custom_types")
     empl = {"id": 1, "name": "John Doe", "salary": 5.0}
-    assert retval == ["daisies", empl, empl]
+    assert retval[1:] == [empl, empl]
+
+    flower = retval[0]
+    assert isinstance(flower, sl.StarlarkEnumValue)
+    assert (flower.enum_type, flower.value, flower.index) \
+        == ("FlowersEnum", "daisies", 0)
+    assert repr(flower) == "FlowersEnum('daisies')"
+
+
+class FlowersEnum(enum.Enum):
+    DAISIES = "daisies"
+    ROSES = "roses"
+
+
+class Weekday(enum.Enum):
+    MONDAY = "monday"
+
+
+class Priority(enum.IntEnum):
+    LOW = 1
+    HIGH = 2
+
+
+ENUMS_STAR = EXT_TYPE_STAR + """
+Priority = enum("low", "high")
+
+def describe(flower):
+    return [type(flower), flower.value, flower.index, flower == FlowersEnum("roses")]
+
+def identity(x):
+    return x
+"""
+
+
+def test_enum_conversion():
+    glb = sl.Globals.extended_by([
+        sl.LibraryExtension.StructType,
+        sl.LibraryExtension.EnumType,
+        sl.LibraryExtension.RecordType,
+        ])
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("enums.star", ENUMS_STAR), glb)
+
+    # Python enum members become values of the Starlark enum of the same name.
+    mod["flower"] = FlowersEnum.ROSES
+    assert sl.eval(mod, sl.parse("check.star", "describe(flower)"), glb) \
+        == ["enum", "roses", 1, True]
+
+    fmod = mod.freeze()
+    assert fmod.call("describe", FlowersEnum.ROSES) == ["enum", "roses", 1, True]
+
+    # Converted enum values round-trip, and are hashable.
+    daisies = fmod.call("identity", FlowersEnum.DAISIES)
+    assert fmod.call("identity", daisies) == daisies
+    assert fmod.call("describe", daisies) == ["enum", "daisies", 0, False]
+    assert {daisies: 1}[daisies] == 1
+
+    class ExtraFlowersEnum(enum.Enum):
+        TULIPS = "tulips"
+    ExtraFlowersEnum.__name__ = "FlowersEnum"
+    with pytest.raises(sl.StarlarkConversionError):
+        fmod.call("identity", ExtraFlowersEnum.TULIPS)
+
+    # Int enum members are checked against their Starlark counterpart, too,
+    # rather than silently becoming ints.
+    with pytest.raises(sl.StarlarkConversionError):
+        fmod.call("identity", Priority.HIGH)
+
+    class Level(enum.IntEnum):
+        HIGH = 2
+    assert fmod.call("identity", Level.HIGH) == 2
+
+    # Without a Starlark counterpart, enums cannot be converted.
+    with pytest.raises(TypeError):
+        fmod.call("identity", Weekday.MONDAY)
+
+
+TUPLES_STAR = """
+def pair(x, y):
+    return (x, y)
+
+def is_tuple(x):
+    return type(x) == "tuple"
+
+def keyed():
+    return {(1, 2): "a"}
+"""
+
+
+def test_preserve_tuples():
+    glb = sl.Globals.standard()
+
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("tuples.star", TUPLES_STAR), glb)
+    fmod = mod.freeze()
+    assert fmod.preserve_tuples is None
+    assert fmod.call("pair", 1, 2) == [1, 2]
+    assert not fmod.call("is_tuple", (1, 2))
+    assert fmod.call_with("pair", (1, 2), preserve_tuples=True) == (1, 2)
+    assert fmod.call_with("is_tuple", [(1, 2)], preserve_tuples=True)
+    assert fmod.call_with("keyed", preserve_tuples=True) == {(1, 2): "a"}
+
+    mod = sl.Module(preserve_tuples=True)
+    mod["t"] = (1, (2, 3))
+    mod.add_callable("swap", lambda t: (t[1], t[0]))
+    assert mod["t"] == (1, (2, 3))
+    assert sl.eval(mod, sl.parse("swap.star", "swap(t)"), glb) == ((2, 3), 1)
+    assert sl.eval(mod, sl.parse("swap.star", "swap(t)"), glb,
+                   preserve_tuples=False) == [[2, 3], 1]
+
+    sl.eval(mod, sl.parse("tuples.star", TUPLES_STAR), glb)
+    fmod = mod.freeze()
+    assert fmod.preserve_tuples
+    assert fmod.call("pair", 1, 2) == (1, 2)
+    assert fmod.call_with("pair", (1, 2), preserve_tuples=False) == [1, 2]
+
+    sl.set_preserve_tuples(True)
+    try:
+        mod = sl.Module()
+        mod["t"] = (1, 2)
+        assert sl.eval(mod, sl.parse("t.star", "t"), glb) == (1, 2)
+    finally:
+        sl.set_preserve_tuples(False)
+
+
+DICT_KEYS_STAR = """
+def keys(d):
+    return [type(k) for k in d]
+
+def lookup(d):
+    return [d[1], d[None], d[False], d[(1, "x")]]
+
+def make():
+    return {1: "a", None: "b", (2, 3): "c", 2.5: "d"}
+"""
+
+
+def test_non_string_dict_keys():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("dict-keys.star", DICT_KEYS_STAR), glb)
+    fmod = mod.freeze()
+
+    d = {1: "a", None: "b", False: "c", (1, "x"): "d", "s": "e"}
+    assert fmod.call("keys", d) == ["int", "NoneType", "bool", "tuple", "string"]
+    assert fmod.call("lookup", d) == ["a", "b", "c", "d"]
+    assert fmod.call("make") == {1: "a", None: "b", (2, 3): "c", 2.5: "d"}
+
+    with pytest.raises(sl.StarlarkConversionError):
+        fmod.call("keys", {sl.OpaquePythonObject(MyObj(5)): 1})
+
+
+BIGINT_STAR = """
+def identity(x):
+    return x
+
+def double(x):
+    return 2 * x
+
+def typename(x):
+    return type(x)
+
+big = 1 << 100
+"""
+
+
+def test_bigint_conversion():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    mod["huge"] = -(2**200)
+    sl.eval(mod, sl.parse("bigint.star", BIGINT_STAR), glb)
+    assert mod["big"] == 2**100
+    assert mod["huge"] == -(2**200)
+    fmod = mod.freeze()
+
+    for value in [0, -1, 2**31, -(2**31) - 1, -(2**40), 2**64 + 1, -(2**100)]:
+        assert fmod.call("identity", value) == value
+        assert fmod.call("double", value) == 2 * value
+        assert fmod.call("typename", value) == "int"
+
+    assert fmod.call("identity", True) is True
+    assert fmod.call("typename", False) == "bool"
+
+
+def test_primitive_conversion():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("bigint.star", BIGINT_STAR), glb)
+    fmod = mod.freeze()
+
+    for value, typename in [
+            (None, "NoneType"), (True, "bool"), (1, "int"), (1.0, "float"),
+            ("s\u00e4\U0001f600", "string"), (float("inf"), "float")]:
+        result = fmod.call("identity", value)
+        assert result == value
+        assert type(result) is type(value)
+        assert fmod.call("typename", value) == typename
+
+    nan = fmod.call("identity", float("nan"))
+    assert isinstance(nan, float)
+    assert nan != nan
+    assert fmod.call("double", -float("inf")) == -float("inf")
+
+
+class Point:
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
+
+
+class Color(enum.IntEnum):
+    RED = 1
+    GREEN = 2
+
+
+CONVERTERS_STAR = """
+def describe(x):
+    return [type(x), x]
+
+def make_point():
+    return struct(x=1, y=2)
+"""
+
+
+def test_converter_registry():
+    registry = sl.ConverterRegistry()
+    registry.register(datetime.date, lambda d: d.isoformat())
+    registry.register(uuid.UUID, str)
+    registry.register(Path, lambda p: p.parts)
+    registry.register(enum.Enum, lambda e: e.name)
+    registry.register(
+        Point, lambda p: {"x": p.x, "y": p.y},
+        lambda d: Point(**d), starlark_type="struct")
+
+    glb = sl.Globals.extended_by([sl.LibraryExtension.StructType])
+    mod = sl.Module(converters=registry)
+    sl.eval(mod, sl.parse("converters.star", CONVERTERS_STAR), glb)
+    mod["today"] = datetime.date(2025, 1, 2)
+    assert mod["today"] == "2025-01-02"
+    fmod = mod.freeze()
+    assert fmod.converters is registry
+
+    u = uuid.UUID(int=5)
+    assert fmod.call("describe", u) == ["string", str(u)]
+    assert fmod.call("describe", Path("/a/b")) == ["list", ["/", "a", "b"]]
+    assert fmod.call("describe", Color.GREEN) == ["string", "GREEN"]
+    assert fmod.call("describe", {"p": Point(3, 4)}) == [
+        "dict", {"p": {"x": 3, "y": 4}}]
+    point = fmod.call("make_point")
+    assert isinstance(point, Point)
+    assert (point.x, point.y) == (1, 2)
+
+    # converters may be given per call
+    assert fmod.call_with(
+        "describe", [Color.RED], converters=sl.ConverterRegistry()
+    ) == ["int", 1]
+
+    # and are used for arguments of Python callables
+    mod = sl.Module()
+    mod.add_callable("origin", lambda: Point(0, 0))
+    assert sl.eval(mod, sl.parse("origin.star", "origin()"), glb,
+                   converters=registry) == {"x": 0, "y": 0}
+
+    with pytest.raises(ValueError):
+        registry.register(Point, str, str)
+
+    bad = sl.ConverterRegistry()
+    bad.register(int, lambda i: i + 1)
+    with pytest.raises(sl.StarlarkConversionError):
+        fmod.call_with("describe", [1], converters=bad)
+
+    # converters returning each other's types do not recurse endlessly
+    class Ping:
+        pass
+
+    class Pong:
+        pass
+
+    looping = sl.ConverterRegistry()
+    looping.register(Ping, lambda _: Pong())
+    looping.register(Pong, lambda _: [Ping()])
+    with pytest.raises(sl.StarlarkConversionError, match="nested conversions"):
+        fmod.call_with("describe", [Ping()], converters=looping)
+
+
+@dataclass
+class Limits:
+    cpu: int
+    memory: str
+
+
+@dataclass
+class Config:
+    name: str
+    limits: Limits
+    tags: list[str]
+
+
+class Version(NamedTuple):
+    major: int
+    minor: int
+
+
+STRUCTS_STAR = """
+def describe(cfg):
+    return [type(cfg), cfg.name, cfg.limits.cpu, cfg.tags[0]]
+
+def version_string(v):
+    return "%d.%d" % (v.major, v.minor)
+
+def identity(x):
+    return x
+
+Employee = record(name=str, salary=float)
+
+def make_employee():
+    return Employee(name="Jane", salary=5.0)
+"""
+
+
+@dataclass
+class Employee:
+    name: str
+    salary: float
+
+
+def test_dataclass_conversion():
+    glb = sl.Globals.extended_by([
+        sl.LibraryExtension.StructType,
+        sl.LibraryExtension.RecordType,
+        ])
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("structs.star", STRUCTS_STAR), glb)
+    fmod = mod.freeze()
+
+    cfg = Config("web", Limits(2, "1G"), ["a"])
+    assert fmod.call("describe", cfg) == ["struct", "web", 2, "a"]
+    assert fmod.call("identity", cfg) == {
+        "name": "web", "limits": {"cpu": 2, "memory": "1G"}, "tags": ["a"]}
+    assert fmod.call("version_string", Version(1, 2)) == "1.2"
+    assert fmod.call("make_employee") == {"name": "Jane", "salary": 5.0}
+
+    registry = sl.ConverterRegistry()
+    registry.register_record(Employee)
+    assert fmod.call_with("make_employee", converters=registry) \
+        == Employee("Jane", 5.0)
+
+    registry = sl.ConverterRegistry()
+    registry.register_record(Limits, record_type="Employee")
+    with pytest.raises(TypeError):
+        fmod.call_with("make_employee", converters=registry)
+
+
+def test_preserve_structs():
+    glb = sl.Globals.extended_by([
+        sl.LibraryExtension.StructType,
+        sl.LibraryExtension.RecordType,
+        ])
+    mod = sl.Module(preserve_structs=True)
+    sl.eval(mod, sl.parse("structs.star", STRUCTS_STAR), glb)
+    fmod = mod.freeze()
+    assert fmod.preserve_structs
+
+    cfg = fmod.call("identity", Config("web", Limits(2, "1G"), ["a"]))
+    assert isinstance(cfg, sl.StarlarkStruct)
+    assert cfg.name == "web"
+    assert cfg.limits.memory == "1G"
+    assert cfg.to_dict()["tags"] == ["a"]
+    assert repr(cfg.limits) == "struct(cpu=2, memory='1G')"
+    with pytest.raises(AttributeError):
+        cfg.nonexistent  # noqa: B018
+    with pytest.raises(AttributeError):
+        cfg.name = "db"
+
+    # Struct views convert back to structs.
+    assert fmod.call("describe", cfg) == ["struct", "web", 2, "a"]
+    assert fmod.call("identity", cfg) == cfg
+
+    emp = fmod.call("make_employee")
+    assert isinstance(emp, sl.StarlarkRecord)
+    assert emp.record_type == "Employee"
+    assert emp.salary == 5.0
+    assert emp.to_dict() == {"name": "Jane", "salary": 5.0}
+    assert repr(emp) == "record[Employee](name='Jane', salary=5.0)"
+
+    assert fmod.call_with("make_employee", preserve_structs=False) \
+        == {"name": "Jane", "salary": 5.0}
+
+
+PROXY_STAR = """
+config = {
+    "servers": [struct(name = "web", ports = [80, 443])],
+    "limits": {"cpu": 2},
+    (1, 2): "pair",
+}
+"""
+
+
+def test_value_proxy():
+    glb = sl.Globals.extended_by([sl.LibraryExtension.StructType])
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("proxy.star", PROXY_STAR), glb)
+    config = mod.freeze().proxy("config")
+    del mod
+
+    assert isinstance(config, sl.StarlarkValue)
+    assert len(config) == 3
+    assert "limits" in config
+    assert "memory" not in config
+    assert config[1, 2] == "pair"
+    with pytest.raises(KeyError):
+        config["memory"]
+
+    servers = config["servers"]
+    assert isinstance(servers, sl.StarlarkValue)
+    web = servers[-1]
+    assert web.name == "web"
+    assert [port for port in web.ports] == [80, 443]
+    assert repr(web.ports) == "[80, 443]"
+    with pytest.raises(AttributeError):
+        web.address  # noqa: B018
+
+    assert config["limits"].to_python() == {"cpu": 2}
+
+
+CALLABLES_STAR = """
+def double(x):
+    return 2 * x
+
+def make_adder(n):
+    return lambda x, y = 0: x + y + n
+
+handlers = {"double": double}
+
+def get_handlers():
+    return handlers
+
+def apply(f, x):
+    return f(x)
+
+def get_py_double():
+    return py_double
+"""
+
+
+def test_starlark_callable():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+
+    def py_double(x):
+        return 2 * x
+
+    mod.add_callable("py_double", py_double)
+    sl.eval(mod, sl.parse("callables.star", CALLABLES_STAR), glb)
+    with pytest.raises(sl.StarlarkConversionError):
+        mod["double"]
+
+    fmod = mod.freeze()
+    double = fmod.call("get_handlers")["double"]
+    assert isinstance(double, sl.StarlarkCallable)
+    assert double(4) == 8
+    assert fmod.call("apply", double, 5) == 10
+    assert fmod.proxy("handlers")["double"](x=3) == 6
+    assert fmod.call("get_py_double") is py_double
+
+    add = fmod.call("make_adder", 1)
+    del fmod
+    assert add(2, y=3) == 6
+
+
+def test_starlark_callable_from_other_module():
+    glb = sl.Globals.standard()
+    stored = []
+    mod_a = sl.Module()
+    mod_a.add_callable("store", stored.append)
+    sl.eval(mod_a, sl.parse("a.star", """
+def pass_to_py(f):
+    store(f)
+
+def get_pass_to_py():
+    return pass_to_py
+"""), glb)
+    pass_to_py = mod_a.freeze().call("get_pass_to_py")
+
+    mod_b = sl.Module()
+    sl.eval(mod_b, sl.parse("b.star", """
+def hello():
+    return "hello"
+
+def get_hello():
+    return hello
+"""), glb)
+    fmod_b = mod_b.freeze()
+    hello = fmod_b.call("get_hello")
+
+    # the stored function keeps the module it came from alive
+    pass_to_py(hello)
+    pass_to_py(pass_to_py)
+    del mod_b, fmod_b, hello
+    gc.collect()
+    assert stored[0]() == "hello"
+    assert isinstance(stored[1], sl.StarlarkCallable)
 
 
 @dataclass
@@ -199,6 +1025,502 @@ def test_opaue_python_obj():
     myobj2 = fmod.call("identity", sl.OpaquePythonObject(myobj))
     assert myobj is myobj2
 
+
+class Account:
+    def __init__(self, owner, balance):
+        self.owner = owner
+        self.balance = balance
+        self.password = "secret"
+
+    def deposit(self, amount):
+        if amount <= 0:
+            raise ValueError("amount must be positive")
+        self.balance += amount
+        return self.balance
+
+
+OPAQUE_STAR = """
+def use(account):
+    return [
+        account.owner,
+        account.deposit(5),
+        account.balance,
+        hasattr(account, "password"),
+        dir(account),
+    ]
+
+def deposit(account, amount):
+    return account.deposit(amount)
+
+def password(account):
+    return account.password
+"""
+
+
+def test_opaque_python_obj_members():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("opaque.star", OPAQUE_STAR), glb)
+    fmod = mod.freeze()
+
+    account = Account("jane", 10)
+    opaque = sl.OpaquePythonObject(
+        account, attrs=["owner", "balance"], methods=["deposit"])
+    assert fmod.call("use", opaque) \
+        == ["jane", 15, 15, False, ["balance", "deposit", "owner"]]
+    assert account.balance == 15
+
+    with pytest.raises(ValueError):
+        fmod.call("deposit", opaque, -1)
+    with pytest.raises(sl.StarlarkEvalError):
+        fmod.call("password", opaque)
+    with pytest.raises(sl.StarlarkEvalError):
+        fmod.call("deposit", sl.OpaquePythonObject(account), 1)
+
+
+class Sensor:
+    @property
+    def reading(self):
+        raise PermissionError(13, "no access")
+
+    @property
+    def position(self):
+        return (1, 2)
+
+
+def test_opaque_python_obj_attr_errors():
+    glb = sl.Globals.extended_by([sl.LibraryExtension.StructType])
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("sensor.star", """
+def reading(sensor):
+    return sensor.reading
+
+def position(sensor):
+    return sensor.position
+
+def has_reading(sensor):
+    return hasattr(sensor, "reading")
+
+def missing(sensor):
+    return sensor.missing
+
+def has_then_struct(sensor):
+    hasattr(sensor, "reading")
+    return struct().reading
+
+def getattr_default(sensor):
+    return getattr(sensor, "reading", None)
+
+def struct_reading():
+    return struct().reading
+"""), glb)
+    fmod = mod.freeze()
+    sensor = sl.OpaquePythonObject(Sensor(), attrs=["reading", "position"])
+
+    # exceptions of attributes propagate rather than hide the attribute
+    with pytest.raises(PermissionError) as exc_info:
+        fmod.call("reading", sensor)
+    assert isinstance(exc_info.value.__cause__, sl.StarlarkEvalError)
+    assert not fmod.call("has_reading", sensor)
+    with pytest.raises(sl.StarlarkEvalError, match="has no attribute"):
+        fmod.call("missing", sensor)
+
+    # swallowed attribute errors do not resurface for other values
+    with pytest.raises(sl.StarlarkEvalError, match="struct"):
+        fmod.call("has_then_struct", sensor)
+    assert fmod.call("getattr_default", sensor) is None
+    with pytest.raises(sl.StarlarkEvalError, match="struct"):
+        fmod.call("struct_reading")
+
+    # attributes are converted with the options of the evaluation
+    assert fmod.call("position", sensor) == [1, 2]
+    assert fmod.call_with("position", [sensor], preserve_tuples=True) == (1, 2)
+
+
+@dataclass(frozen=True)
+class Handle:
+    id: int
+
+
+OPAQUE_EQ_STAR = """
+def count_distinct(handles):
+    return len({h: None for h in handles})
+
+def same(a, b):
+    return a == b
+
+def show(h):
+    return repr(h)
+"""
+
+
+def test_opaque_python_obj_eq_hash():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("opaque-eq.star", OPAQUE_EQ_STAR), glb)
+    fmod = mod.freeze()
+
+    def wrap(*objs, **kwargs):
+        return [sl.OpaquePythonObject(obj, **kwargs) for obj in objs]
+
+    assert fmod.call("count_distinct", wrap(Handle(1), Handle(1), Handle(2))) == 2
+    assert fmod.call("same", *wrap(Handle(1), Handle(1)))
+    assert fmod.call("show", *wrap(Handle(1))) == "Handle(id=1)"
+    with pytest.raises(TypeError):
+        fmod.call("count_distinct", wrap(MyObj(1)))
+
+    handle = Handle(1)
+    assert fmod.call("same", *wrap(handle, handle, fully_opaque=True))
+    assert not fmod.call("same", *wrap(handle, Handle(1), fully_opaque=True))
+    assert fmod.call(
+        "count_distinct", wrap(handle, handle, MyObj(1), fully_opaque=True)) == 2
+
+    # fully opaque objects do not equal ones that are not
+    mixed = [sl.OpaquePythonObject(handle, fully_opaque=True),
+             sl.OpaquePythonObject(handle)]
+    assert not fmod.call("same", *mixed)
+    assert fmod.call("count_distinct", mixed) == 2
+    assert fmod.call("show", *wrap(handle, fully_opaque=True)) \
+        == "<opaque python object>"
+
+
+@sl.native_type(attrs=["name", "deps"], methods=["output"])
+@dataclass(frozen=True)
+class Target:
+    name: str
+    deps: tuple[str, ...] = ()
+    secret: str = "hidden"
+
+    def output(self, suffix: str = ".o") -> str:
+        return self.name + suffix
+
+
+NATIVE_STAR = """
+def describe(t: Target) -> str:
+    return t.output() + " " + t.output(suffix=".so") + " " + ",".join(t.deps)
+
+lib = Target(name="lib")
+"""
+
+
+def test_native_type():
+    assert Target.__starlark_type__.name == "Target"
+
+    builder = sl.GlobalsBuilder.standard()
+    builder.set("Target", Target)
+    glb = builder.build()
+
+    mod = sl.Module()
+    mod["app"] = Target("app", ("lib",))
+    sl.eval(mod, sl.parse("native.star", NATIVE_STAR, sl.Dialect.extended()), glb)
+    assert sl.eval(mod, sl.parse("main.star", "describe(app)"), glb) \
+        == "app.o app.so lib"
+    assert sl.eval(mod, sl.parse("main.star", "repr(app)"), glb) == repr(mod["app"])
+    assert sl.eval(mod, sl.parse("main.star", "{lib: 1}[Target('lib')]"), glb) == 1
+    assert mod["lib"] == Target("lib")
+    assert sl.eval(mod, sl.parse("main.star", "Target"), glb) is Target
+
+    # subclasses are not of the native type, so a dataclass becomes a struct
+    @dataclass(frozen=True)
+    class SubTarget(Target):
+        pass
+    mod["sub"] = SubTarget("sub")
+    assert sl.eval(mod, sl.parse("main.star", "type(sub)"), glb) == "struct"
+
+    for call in ["describe('app')", "app.secret", "app.output(1, 2)", "Target()"]:
+        with pytest.raises(sl.StarlarkEvalError):
+            sl.eval(mod, sl.parse("main.star", call), glb)
+
+    dialect = sl.Dialect.extended()
+    dialect.enable_types = sl.DialectTypes.ENABLE
+    for body, n_errs in [
+        ("t.output(suffix='.a').upper()", 0),
+        ("t.output(1)", 1),
+        ("t.secret", 1),
+        ("Target(name=1)", 1),
+        ("t.name.append('x')", 1),
+    ]:
+        ast = sl.parse("tc.star", f"def f(t: Target):\n    {body}\n", dialect)
+        errs, _iface, _ = ast.typecheck(glb, {})
+        assert len(errs) == n_errs, body
+
+# }}}
+
+
+# {{{ errors
+
+def test_syntax_error():
+    with pytest.raises(sl.StarlarkSyntaxError) as exc_info:
+        sl.parse("syntax.star", "x = 1\ny = = 2")
+
+    span = exc_info.value.error.span
+    assert span.file == "syntax.star"
+    assert span.span.begin.line == 1
+
+
+ERRORS_STAR = """
+def f(x):
+    return x + "a"
+
+def g():
+    fail("oops")
+"""
+
+
+def test_eval_errors():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("errors.star", ERRORS_STAR), glb)
+    fmod = mod.freeze()
+
+    with pytest.raises(sl.StarlarkTypeError) as exc_info:
+        fmod.call("f", 1)
+    assert isinstance(exc_info.value, sl.StarlarkEvalError)
+    assert exc_info.value.error.span.span.begin.line == 2
+
+    with pytest.raises(sl.StarlarkEvalError) as exc_info:
+        fmod.call("g")
+    assert "oops" in str(exc_info.value.error)
+
+    # other errors of values are not type errors
+    for src in ['{}["x"]', "1 // 0", "[1][5]"]:
+        with pytest.raises(sl.StarlarkEvalError) as exc_info:
+            sl.eval(mod, sl.parse("value-errors.star", src), glb)
+        assert not isinstance(exc_info.value, sl.StarlarkTypeError)
+    with pytest.raises(sl.StarlarkTypeError):
+        sl.eval(mod, sl.parse("value-errors.star", "len(1)"), glb)
+
+
+CALLS_STAR = """
+def h(x):
+    return f(x)
+"""
+
+
+def test_call_stack():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("errors.star", ERRORS_STAR + CALLS_STAR), glb)
+
+    with pytest.raises(sl.StarlarkTypeError) as exc_info:
+        sl.eval(mod, sl.parse("main.star", "x = 1\nh(x)"), glb)
+    call_stack = exc_info.value.call_stack
+    assert [frame.name for frame in call_stack] == ["h", "f"]
+    assert call_stack[0].location.file == "main.star"
+    assert call_stack[0].location.span.begin.line == 1
+    assert call_stack[1].location.file == "errors.star"
+
+
+def test_starlark_traceback():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("errors.star", ERRORS_STAR + CALLS_STAR), glb)
+
+    sl.set_starlark_tracebacks(True)
+    try:
+        with pytest.raises(sl.StarlarkTypeError) as exc_info:
+            sl.eval(mod, sl.parse("main.star", "x = 1\nh(x)"), glb)
+    finally:
+        sl.set_starlark_tracebacks(False)
+
+    entries = [
+        (entry.filename, entry.lineno, entry.name)
+        for entry in traceback.extract_tb(exc_info.value.__traceback__)
+        if entry.filename.endswith(".star")]
+    assert entries == [
+        ("main.star", 2, "<module>"),
+        ("errors.star", 9, "h"),
+        ("errors.star", 3, "f"),
+    ]
+    assert "return x + " in "".join(traceback.format_tb(exc_info.value.__traceback__))
+
+
+def test_conversion_error():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    with pytest.raises(sl.StarlarkConversionError) as exc_info:
+        sl.eval(mod, sl.parse("conversion.star", "range(3)"), glb)
+    assert exc_info.value.error.span is None
+
+# }}}
+
+
+# {{{ resource limits
+
+LOOP_STAR = """
+def loop(n):
+    total = 0
+    for i in range(n):
+        total += i
+    return total
+
+loop(10)
+"""
+
+
+def test_step_limit():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    ast = sl.parse("loop.star", LOOP_STAR)
+
+    with pytest.raises(sl.StepLimitExceeded):
+        sl.eval(mod, ast, glb, max_steps=10)
+
+    mod = sl.Module()
+    assert sl.eval(mod, ast, glb, max_steps=1000) == 45
+
+
+def test_step_limit_call():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("loop.star", LOOP_STAR), glb)
+    fmod = mod.freeze()
+
+    assert fmod.call_with("loop", [100], max_steps=1000) == 4950
+
+    with pytest.raises(sl.StepLimitExceeded):
+        fmod.call_with("loop", [10**6], max_steps=1000)
+
+    # the step limit is a subclass of the generic error
+    with pytest.raises(sl.StarlarkError):
+        fmod.call_with("loop", kwargs={"n": 10**6}, max_steps=1000)
+
+
+def test_timeout():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("loop.star", LOOP_STAR), glb)
+
+    start = time.monotonic()
+    with pytest.raises(sl.EvaluationCancelled):
+        sl.eval(mod, sl.parse("timeout.star", "loop(1000000000)"), glb, timeout=0.1)
+    assert time.monotonic() - start < 5
+
+    fmod = mod.freeze()
+    assert fmod.call_with("loop", [10], timeout=10) == 45
+    with pytest.raises(sl.EvaluationCancelled):
+        fmod.call_with("loop", [10**9], timeout=0.1)
+
+    # the timeout is checked between statements, so a single slow statement
+    # runs to completion
+    mod = sl.Module()
+    mod.add_callable("nap", lambda: time.sleep(0.3) or "rested")
+    assert sl.eval(mod, sl.parse("nap.star", "nap()"), glb, timeout=0.05) \
+        == "rested"
+    with pytest.raises(sl.EvaluationCancelled):
+        sl.eval(mod, sl.parse("nap.star", "nap()\nnap()"), glb, timeout=0.05)
+
+
+def test_cancellation_token():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("loop.star", LOOP_STAR), glb)
+
+    token = sl.CancellationToken()
+    assert not token.cancelled
+    timer = threading.Timer(0.1, token.cancel)
+    timer.start()
+    try:
+        with pytest.raises(sl.EvaluationCancelled):
+            sl.eval(mod, sl.parse("cancel.star", "loop(1000000000)"), glb,
+                    cancellation_token=token)
+    finally:
+        timer.cancel()
+    assert token.cancelled
+
+    # an already-cancelled token stops the evaluation right away
+    fmod = mod.freeze()
+    with pytest.raises(sl.EvaluationCancelled):
+        fmod.call_with("loop", [10], cancellation_token=token)
+
+
+GROW_STAR = """
+def grow(n):
+    result = []
+    for i in range(n):
+        result.append("item-%d" % i)
+    return result
+"""
+
+
+def test_heap_limit():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("grow.star", GROW_STAR), glb)
+
+    with pytest.raises(sl.MemoryLimitExceeded):
+        sl.eval(mod, sl.parse("grow.star", "x = grow(100000)"), glb,
+                max_heap_bytes=100_000)
+
+    fmod = mod.freeze()
+    assert len(fmod.call_with("grow", [10], max_heap_bytes=100_000)) == 10
+    with pytest.raises(sl.MemoryLimitExceeded):
+        fmod.call_with("grow", [100000], max_heap_bytes=100_000)
+
+    # allocations by the last statement are caught as well
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("big.star", """
+def big():
+    return "x" * 50000000
+"""), glb)
+    fmod = mod.freeze()
+    with pytest.raises(sl.MemoryLimitExceeded):
+        fmod.call_with("big", max_heap_bytes=1000)
+    with pytest.raises(sl.MemoryLimitExceeded):
+        sl.eval(sl.Module(), sl.parse("big.star", '"x" * 50000000'), glb,
+                max_heap_bytes=1000)
+
+
+def test_heap_size():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    size_before = mod.heap_size()
+    sl.eval(mod, sl.parse("grow.star", GROW_STAR + "x = grow(1000)"), glb)
+    assert mod.heap_size() > size_before
+
+    summary = mod.heap_summary()
+    count, nbytes = summary["list"]
+    assert count >= 1
+    assert nbytes > 0
+
+    fmod = mod.freeze()
+    assert fmod.memory_flamegraph()
+
+
+RECURSE_STAR = """
+def recurse(n):
+    if n == 0:
+        return 0
+    return recurse(n - 1) + 1
+"""
+
+
+def test_callstack_depth():
+    glb = sl.Globals.standard()
+    mod = sl.Module()
+    sl.eval(mod, sl.parse("recurse.star", RECURSE_STAR), glb)
+
+    # the default limit of starlark-rust is lower than this
+    assert sl.eval(mod, sl.parse("deep.star", "recurse(150)"), glb,
+                   max_callstack_depth=200) == 150
+
+    with pytest.raises(sl.StackOverflowError) as exc_info:
+        sl.eval(mod, sl.parse("deep.star", "recurse(10)"), glb,
+                max_callstack_depth=5)
+    call_stack = exc_info.value.call_stack
+    assert len(call_stack) == 5
+    assert all(frame.name == "recurse" for frame in call_stack)
+    assert call_stack[0].location.file == "deep.star"
+    assert call_stack[-1].location.file == "recurse.star"
+
+    fmod = mod.freeze()
+    assert fmod.call_with("recurse", [5], max_callstack_depth=6) == 5
+    with pytest.raises(sl.StackOverflowError):
+        fmod.call_with("recurse", [6], max_callstack_depth=6)
+
+    with pytest.raises(ValueError):
+        fmod.call_with("recurse", [6], max_callstack_depth=0)
+
 # }}}
 
 